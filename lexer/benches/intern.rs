@@ -0,0 +1,74 @@
+//! Compares allocations and time when lexing a file full of repeated identifiers,
+//! with and without interning. Run with `cargo bench --bench intern`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use lexer::tokens::TokenKind;
+use lexer::{Lexer, LexerOptions};
+
+/// Allocator that counts every allocation made through it.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Build a source file that reuses a handful of names many times.
+fn source() -> String {
+    let names = ["count", "total", "index", "value", "result"];
+    (0..2_000)
+        .map(|i| {
+            let a = names[i % names.len()];
+            let b = names[(i + 1) % names.len()];
+            format!("var {a} = {b} + {a} * {b}\n")
+        })
+        .collect()
+}
+
+/// Lex the whole input, returning the number of allocations and the time taken.
+fn measure(input: &str, options: LexerOptions) -> (usize, Duration) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    let mut lexer = Lexer::from_str(input).with_options(options);
+    loop {
+        let token = lexer.next_token().expect("benchmark input is valid");
+        if token.kind == TokenKind::Eof {
+            break;
+        }
+        black_box(token);
+    }
+    black_box(lexer.into_interner());
+
+    let elapsed = start.elapsed();
+    (ALLOCATIONS.load(Ordering::Relaxed) - before, elapsed)
+}
+
+fn main() {
+    let input = source();
+    let interned = LexerOptions {
+        intern_identifiers: true,
+        ..LexerOptions::default()
+    };
+
+    let (plain_allocs, plain_time) = measure(&input, LexerOptions::default());
+    let (interned_allocs, interned_time) = measure(&input, interned);
+
+    println!("identifiers as strings: {plain_allocs:>6} allocations in {plain_time:?}");
+    println!("interned identifiers:   {interned_allocs:>6} allocations in {interned_time:?}");
+}
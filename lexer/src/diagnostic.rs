@@ -0,0 +1,74 @@
+use crate::source::SourceMap;
+use crate::span::Span;
+
+/// Render an error `message` for `span` in `source`, showing the offending line
+/// with a caret underline beneath the spanned text.
+/// Spans covering several lines are underlined to the end of their first line.
+/// Tabs in the line are expanded so the caret lines up in a terminal.
+pub fn render(source: &str, span: Span, message: &str) -> String {
+    let map = SourceMap::new(source);
+    let start = map.line_col(span.start);
+    let line = map.line_index(span.start);
+    let text = map.expand_tabs(map.line_text(line).unwrap_or_default());
+
+    // Underline at least one character, even for empty spans
+    let end = map.line_col(span.end);
+    let end_col = if end.line == start.line {
+        end.col
+    } else {
+        text.chars().count() + 1
+    };
+    let width = end_col.saturating_sub(start.col).max(1);
+
+    let gutter = " ".repeat(start.line.to_string().len());
+    format!(
+        "error: {message}\n\
+         {gutter}--> {start}\n\
+         {gutter} |\n\
+         {line} | {text}\n\
+         {gutter} | {pad}{carets}\n",
+        line = start.line,
+        pad = " ".repeat(start.col - 1),
+        carets = "^".repeat(width),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_single_char() {
+        let source = "val x = 5\nval y = @\n";
+        let output = render(source, Span::new(18, 19), "unexpected character '@'");
+        assert_eq!(
+            output,
+            "error: unexpected character '@'\n \
+             --> 2:9\n  \
+             |\n\
+             2 | val y = @\n  \
+             |         ^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_token_width() {
+        let source = "fn add(a: int): int { a + b }";
+        let output = render(source, Span::new(3, 6), "unknown function");
+        assert!(output.ends_with("1 | fn add(a: int): int { a + b }\n  |    ^^^\n"));
+    }
+
+    #[test]
+    fn test_render_multiline_span() {
+        let source = "val x = (\n5)";
+        let output = render(source, Span::new(8, 12), "unclosed group");
+        assert!(output.ends_with("1 | val x = (\n  |         ^\n"));
+    }
+
+    #[test]
+    fn test_render_with_tabs() {
+        let source = "\tval x = @";
+        let output = render(source, Span::new(9, 10), "unexpected character '@'");
+        assert!(output.ends_with("1 |     val x = @\n  |             ^\n"));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::span::Span;
+use crate::tokens::TokenKind;
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexError {
+    /// A character that cannot start any token.
+    UnexpectedChar { ch: char, span: Span },
+    /// A character that only starts a token as part of one of the `expected` operators,
+    /// such as a lone `&` where `&&` was meant.
+    ExpectedOneOf {
+        ch: char,
+        expected: &'static [&'static str],
+        span: Span,
+    },
+    /// An integer literal too large to fit in an `isize`.
+    IntOverflow { span: Span },
+    /// Indentation that mixes tabs and spaces inconsistently with the enclosing blocks,
+    /// or that returns to a level no enclosing block has.
+    InconsistentIndent { span: Span },
+    /// A string literal missing its closing quote, spanning from the opening quote to the end of input.
+    UnterminatedString { span: Span },
+    /// An escape sequence that is not recognised.
+    InvalidEscape { span: Span },
+    /// An identifier or integer literal longer than the configured maximum token length.
+    TokenTooLong { span: Span },
+    /// Input following the token of something expected to be a single token.
+    TrailingInput { span: Span },
+    /// A character literal that is empty, holds more than one character, or is missing its closing quote.
+    InvalidCharLiteral { span: Span },
+}
+
+impl LexError {
+    /// Get the span of the input that caused the error.
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnexpectedChar { span, .. }
+            | LexError::ExpectedOneOf { span, .. }
+            | LexError::IntOverflow { span }
+            | LexError::InconsistentIndent { span }
+            | LexError::UnterminatedString { span }
+            | LexError::InvalidEscape { span }
+            | LexError::TokenTooLong { span }
+            | LexError::TrailingInput { span }
+            | LexError::InvalidCharLiteral { span } => *span,
+        }
+    }
+
+    /// Get mutable access to the span of the input that caused the error.
+    pub(crate) fn span_mut(&mut self) -> &mut Span {
+        match self {
+            LexError::UnexpectedChar { span, .. }
+            | LexError::ExpectedOneOf { span, .. }
+            | LexError::IntOverflow { span }
+            | LexError::InconsistentIndent { span }
+            | LexError::UnterminatedString { span }
+            | LexError::InvalidEscape { span }
+            | LexError::TokenTooLong { span }
+            | LexError::TrailingInput { span }
+            | LexError::InvalidCharLiteral { span } => span,
+        }
+    }
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexError::UnexpectedChar { ch, span } => {
+                write!(f, "unexpected character '{}' at {}", ch, span)
+            }
+            LexError::ExpectedOneOf { ch, expected, span } => {
+                write!(f, "unexpected character '{}' at {}, expected ", ch, span)?;
+                if let [only] = expected {
+                    write!(f, "'{}'", only)
+                } else {
+                    let expected: Vec<_> = expected.iter().map(|op| format!("'{}'", op)).collect();
+                    write!(f, "one of {}", expected.join(", "))
+                }
+            }
+            LexError::IntOverflow { span } => {
+                write!(f, "integer literal out of range at {}", span)
+            }
+            LexError::InconsistentIndent { span } => {
+                write!(f, "inconsistent indentation at {}", span)
+            }
+            LexError::UnterminatedString { span } => {
+                write!(f, "unterminated string literal at {}", span)
+            }
+            LexError::InvalidEscape { span } => {
+                write!(f, "invalid escape sequence at {}", span)
+            }
+            LexError::TokenTooLong { span } => {
+                write!(f, "token too long at {}", span)
+            }
+            LexError::TrailingInput { span } => {
+                write!(f, "unexpected input after the token at {}", span)
+            }
+            LexError::InvalidCharLiteral { span } => {
+                write!(f, "invalid character literal at {}", span)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A token other than the one the parser required.
+    UnexpectedToken {
+        expected: TokenKind,
+        found: TokenKind,
+        span: Span,
+    },
+    /// A token that cannot start an expression where one is required.
+    ExpectedExpression { found: TokenKind, span: Span },
+    /// A comparison following another at the same level, as in `a == b == c`,
+    /// spanning the second comparison operator.
+    ChainedComparison { operator: TokenKind, span: Span },
+}
+
+impl ParseError {
+    /// Get the span of the token that caused the error.
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::ExpectedExpression { span, .. }
+            | ParseError::ChainedComparison { span, .. } => *span,
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedToken {
+                expected,
+                found,
+                span,
+            } => write!(f, "expected '{}', found '{}' at {}", expected, found, span),
+            ParseError::ExpectedExpression { found, span } => {
+                write!(f, "expected an expression, found '{}' at {}", found, span)
+            }
+            ParseError::ChainedComparison { operator, span } => write!(
+                f,
+                "comparison '{}' cannot follow another comparison at {}, add parentheses or '&&'",
+                operator, span
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_display() {
+        let error = LexError::UnexpectedChar {
+            ch: '@',
+            span: Span::new(4, 5),
+        };
+        assert_eq!(error.to_string(), "unexpected character '@' at [4..5]");
+        assert_eq!(error.span(), Span::new(4, 5));
+    }
+
+    #[test]
+    fn test_expected_one_of_display() {
+        let error = LexError::ExpectedOneOf {
+            ch: '&',
+            expected: &["&&"],
+            span: Span::new(2, 3),
+        };
+        assert_eq!(
+            error.to_string(),
+            "unexpected character '&' at [2..3], expected '&&'"
+        );
+
+        let error = LexError::ExpectedOneOf {
+            ch: '<',
+            expected: &["<=", "<<"],
+            span: Span::new(0, 1),
+        };
+        assert_eq!(
+            error.to_string(),
+            "unexpected character '<' at [0..1], expected one of '<=', '<<'"
+        );
+    }
+
+    #[test]
+    fn test_overflow_display() {
+        let error = LexError::IntOverflow {
+            span: Span::new(0, 20),
+        };
+        assert_eq!(error.to_string(), "integer literal out of range at [0..20]");
+    }
+
+    #[test]
+    fn test_parse_error_display() {
+        let error = ParseError::UnexpectedToken {
+            expected: TokenKind::RParen,
+            found: TokenKind::Comma,
+            span: Span::new(6, 7),
+        };
+        assert_eq!(error.to_string(), "expected ')', found ',' at [6..7]");
+        assert_eq!(error.span(), Span::new(6, 7));
+
+        let error = ParseError::ExpectedExpression {
+            found: TokenKind::RParen,
+            span: Span::new(4, 5),
+        };
+        assert_eq!(
+            error.to_string(),
+            "expected an expression, found ')' at [4..5]"
+        );
+
+        let error = ParseError::ChainedComparison {
+            operator: TokenKind::LessThan,
+            span: Span::new(7, 8),
+        };
+        assert_eq!(
+            error.to_string(),
+            "comparison '<' cannot follow another comparison at [7..8], add parentheses or '&&'"
+        );
+    }
+}
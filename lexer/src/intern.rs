@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// An interned string, resolved back to its text by the [`Interner`] that produced it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Deduplicating string storage, handing out one [`Symbol`] per distinct string.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    symbols: HashMap<Box<str>, Symbol>,
+    strings: Vec<Box<str>>,
+}
+
+impl Interner {
+    /// Create an empty interner.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the symbol for `text`, storing it if it has not been seen before.
+    /// Only allocates the first time a string is interned.
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(text) {
+            return symbol;
+        }
+        let index = u32::try_from(self.strings.len()).expect("too many interned strings");
+        let symbol = Symbol(index);
+        self.strings.push(text.into());
+        self.symbols.insert(text.into(), symbol);
+        symbol
+    }
+
+    /// Get the text of `symbol`.
+    /// Returns `None` if the symbol came from a different interner.
+    pub fn resolve(&self, symbol: Symbol) -> Option<&str> {
+        self.strings.get(symbol.0 as usize).map(|text| &**text)
+    }
+
+    /// Forget every string interned after the first `len`.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        for text in self.strings.drain(len.min(self.strings.len())..) {
+            self.symbols.remove(&text);
+        }
+    }
+
+    /// Get the number of distinct strings interned.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Check if nothing has been interned.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_deduplicates() {
+        let mut interner = Interner::new();
+        let a = interner.intern("count");
+        let b = interner.intern("total");
+        let c = interner.intern("count");
+
+        assert_eq!(a, c);
+        assert_ne!(a, b);
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut interner = Interner::new();
+        let symbol = interner.intern("count");
+
+        assert_eq!(interner.resolve(symbol), Some("count"));
+        assert_eq!(Interner::new().resolve(symbol), None);
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut interner = Interner::new();
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        interner.truncate(1);
+
+        assert_eq!(interner.len(), 1);
+        assert_eq!(interner.resolve(a), Some("a"));
+        assert_eq!(interner.resolve(b), None);
+        assert_eq!(interner.intern("c"), b);
+    }
+
+    #[test]
+    fn test_symbol_display() {
+        let mut interner = Interner::new();
+        interner.intern("a");
+        assert_eq!(interner.intern("b").to_string(), "#1");
+    }
+}
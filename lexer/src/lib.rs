@@ -1,47 +1,325 @@
 #![allow(dead_code)]
 
-use phf::phf_map;
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
 
+use error::LexError;
+use intern::Interner;
+use span::{FileId, FileSpan, Span};
 use tokens::Token;
 
+pub mod diagnostic;
+pub mod error;
+pub mod intern;
+pub mod source;
 pub mod span;
+pub mod stats;
+pub mod stream;
+pub mod streaming;
 pub mod tokens;
 
-/// Static map for keywords
-static KEYWORDS: phf::Map<&'static str, tokens::TokenKind> = phf_map! {
-    "val" => tokens::TokenKind::Val,
-    "var" => tokens::TokenKind::Var,
-    "fn" => tokens::TokenKind::Fn,
-    "if" => tokens::TokenKind::If,
-    "elif" => tokens::TokenKind::Elif,
-    "else" => tokens::TokenKind::Else,
-};
-
-struct Lexer {
-    pub input: String,
-    pub position: usize,
+/// Everything about a lexer that changes as it reads, so lexing can be undone.
+#[derive(Debug, Clone)]
+struct LexerState {
+    position: usize,
+    pending: VecDeque<Result<Token, LexError>>,
+    /// The indentation stack, or `None` if it was left out as lexing cannot change it.
+    indents: Option<Vec<String>>,
+    at_line_start: bool,
+    depth: usize,
+    /// Number of strings interned, so names interned since can be forgotten.
+    interned: usize,
 }
 
-impl Lexer {
+/// A saved position in a lexer's input, for rewinding after speculatively lexing ahead.
+/// Created by [`Lexer::checkpoint`] and only valid for the lexer that created it.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    /// Identity of the lexer that created the checkpoint.
+    lexer: u64,
+    /// Characters dropped from the input when the checkpoint was created.
+    base: usize,
+    state: LexerState,
+}
+
+/// Marks the encoding at the start of some files, and is skipped there.
+const BYTE_ORDER_MARK: char = '\u{FEFF}';
+
+/// Source of a distinct identity for every lexer, so checkpoints cannot be mixed up.
+static NEXT_LEXER_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Options controlling which tokens the lexer produces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LexerOptions {
+    /// Emit each run of whitespace as a `Whitespace` token instead of skipping it.
+    pub emit_whitespace: bool,
+    /// Emit ordinary line comments as `Comment` tokens instead of skipping them.
+    /// Comment-only lines are still skipped in indentation mode.
+    pub emit_comments: bool,
+    /// Derive block structure from indentation, emitting `Newline` at the end of each
+    /// logical line and `Indent`/`Dedent` when the indentation level changes.
+    /// Blank and comment-only lines are ignored, and lines inside parentheses, brackets and braces
+    /// are joined, so an expression can wrap without opening a block.
+    pub indentation: bool,
+    /// Produce `InternedIdentifier` tokens holding a symbol from the lexer's interner,
+    /// instead of `Identifier` tokens that each allocate their name.
+    pub intern_identifiers: bool,
+    /// Produce a `BigIntLiteral` holding the digits of an integer literal too large for an `isize`,
+    /// instead of an `IntOverflow` error.
+    pub big_integers: bool,
+    /// Emit a `Newline` at the end of each line, for grammars that end statements with newlines.
+    /// Lines inside parentheses, brackets and braces are joined, so expressions can wrap.
+    /// Blank and comment-only lines produce no `Newline`.
+    /// Indentation mode already emits newlines, and takes precedence.
+    pub newlines: bool,
+    /// The most characters an identifier, keyword or integer literal may have, or `None` for no limit.
+    /// Longer ones produce a `TokenTooLong` error, and are skipped without being stored.
+    pub max_token_len: Option<usize>,
+}
+
+pub struct Lexer<'a> {
+    /// Text being lexed, read with [`Lexer::rest`] and replaced with [`Lexer::relex`].
+    input: Cow<'a, str>,
+    /// Byte offset of the next character to lex, read with [`Lexer::offset`].
+    position: usize,
+    pub options: LexerOptions,
+    /// File that the spans of tokens and errors refer to, attached by [`Lexer::file_span`].
+    pub file: FileId,
+    /// Number of bytes dropped from the front of the input, added to every span.
+    /// Only non-zero when the input is a window over a longer stream.
+    base: usize,
+    /// Tokens and errors already produced but not yet returned.
+    pending: VecDeque<Result<Token, LexError>>,
+    /// Indentation of each open block, innermost last. Only used in indentation mode.
+    indents: Vec<String>,
+    /// Whether no token has been produced on the current line yet.
+    at_line_start: bool,
+    /// Number of open parentheses, brackets and braces. Only used in newline and indentation modes.
+    depth: usize,
+    /// Buffer holding the most recently read word, reused to avoid allocating per identifier.
+    word: String,
+    /// Storage for interned identifiers.
+    interner: Interner,
+    /// Words lexed as `CustomKeyword` rather than identifiers, besides the built-in keywords.
+    extra_keywords: HashSet<String>,
+    /// Identity of this lexer, recorded in its checkpoints.
+    id: u64,
+}
+
+impl<'a> Lexer<'a> {
+    /// Create a lexer that takes ownership of the input.
     pub fn new(input: String) -> Self {
-        Lexer { input, position: 0 }
+        Self::with_input(Cow::Owned(input))
+    }
+
+    /// Create a lexer that takes ownership of the input, and lexes the words in `extra` as keywords.
+    /// See [`Lexer::with_keywords`].
+    pub fn new_with_keywords(input: String, extra: &[&str]) -> Self {
+        Self::new(input).with_keywords(extra)
+    }
+
+    /// Create a lexer that borrows the input, avoiding a copy of the source.
+    // `FromStr` cannot borrow from its input, so this is an inherent method instead
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &'a str) -> Self {
+        Self::with_input(Cow::Borrowed(input))
+    }
+
+    fn with_input(input: Cow<'a, str>) -> Self {
+        Lexer {
+            base: 0,
+            input,
+            position: 0,
+            options: LexerOptions::default(),
+            file: FileId::default(),
+            pending: VecDeque::new(),
+            indents: Vec::new(),
+            at_line_start: true,
+            depth: 0,
+            word: String::new(),
+            interner: Interner::new(),
+            extra_keywords: HashSet::new(),
+            id: NEXT_LEXER_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Set the options controlling which tokens are produced.
+    pub fn with_options(mut self, options: LexerOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set the file that the spans of tokens and errors refer to.
+    pub fn with_file(mut self, file: FileId) -> Self {
+        self.file = file;
+        self
+    }
+
+    /// Attach the lexer's file to the span of one of its tokens or errors.
+    pub fn file_span(&self, span: Span) -> FileSpan {
+        span.in_file(self.file)
+    }
+
+    /// Reserve the words in `extra` as keywords, producing `CustomKeyword` tokens for them.
+    /// The built-in keywords take precedence, and words that are not valid identifiers never match.
+    pub fn with_keywords(mut self, extra: &[&str]) -> Self {
+        self.extra_keywords
+            .extend(extra.iter().map(|word| word.to_string()));
+        self
+    }
+
+    /// Get the interner holding the names of interned identifiers.
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+
+    /// Take the interner out of the lexer, once lexing is done.
+    pub fn into_interner(self) -> Interner {
+        self.interner
+    }
+
+    /// Get the part of the input that has not been lexed yet.
+    /// Empty once the lexer reaches `Eof`.
+    pub fn rest(&self) -> &str {
+        &self.input[self.position..]
+    }
+
+    /// Get the current position in the input, in the same units as token spans.
+    pub fn offset(&self) -> usize {
+        self.base + self.position
+    }
+
+    /// Save the current position, so lexing can later resume from here with [`Lexer::restore`].
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            lexer: self.id,
+            base: self.base,
+            state: self.save(),
+        }
+    }
+
+    /// Rewind to a position saved by [`Lexer::checkpoint`].
+    /// The tokens after the checkpoint are produced again, exactly as before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the checkpoint was created by a different lexer, for input since replaced by
+    /// [`Lexer::relex`], or before consumed input was dropped from this one,
+    /// as its position would then refer to different text.
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        assert_eq!(
+            checkpoint.lexer, self.id,
+            "checkpoint was created by a different lexer"
+        );
+        assert_eq!(
+            checkpoint.base, self.base,
+            "checkpoint refers to input that has been discarded"
+        );
+        self.restore_state(checkpoint.state);
+    }
+
+    /// Rewind to the start of the input, as if nothing had been lexed.
+    /// Names already interned are kept.
+    pub fn reset(&mut self) {
+        self.position = 0;
+        self.pending.clear();
+        self.indents.clear();
+        self.at_line_start = true;
+        self.depth = 0;
+    }
+
+    /// Replace the input with `input` and start lexing it from the beginning.
+    /// Options, extra keywords and interned names are kept, so symbols stay comparable across inputs.
+    /// Checkpoints into the previous input can no longer be restored.
+    pub fn relex(&mut self, input: String) {
+        self.input = Cow::Owned(input);
+        self.base = 0;
+        self.id = NEXT_LEXER_ID.fetch_add(1, Ordering::Relaxed);
+        self.reset();
+    }
+
+    /// Capture the state of the lexer.
+    fn save(&self) -> LexerState {
+        LexerState {
+            position: self.position,
+            pending: self.pending.clone(),
+            indents: Some(self.indents.clone()),
+            at_line_start: self.at_line_start,
+            depth: self.depth,
+            interned: self.interner.len(),
+        }
+    }
+
+    /// Capture enough state to undo lexing the next token, which is cheaper than [`Lexer::save`].
+    /// The indentation stack only changes at the start of a line, so it is only copied there.
+    fn save_token(&self) -> LexerState {
+        let reads_indentation = self.options.indentation && self.at_line_start;
+        LexerState {
+            position: self.position,
+            pending: self.pending.clone(),
+            indents: reads_indentation.then(|| self.indents.clone()),
+            at_line_start: self.at_line_start,
+            depth: self.depth,
+            interned: self.interner.len(),
+        }
+    }
+
+    /// Return the lexer to a state captured by [`Lexer::save`] or [`Lexer::save_token`].
+    fn restore_state(&mut self, state: LexerState) {
+        self.position = state.position;
+        self.pending = state.pending;
+        if let Some(indents) = state.indents {
+            self.indents = indents;
+        }
+        self.at_line_start = state.at_line_start;
+        self.depth = state.depth;
+        self.interner.truncate(state.interned);
+    }
+
+    /// Check if tokens are queued to be produced before any more input is read.
+    fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Append more text to the end of the input.
+    fn push_input(&mut self, text: &str) {
+        self.input.to_mut().push_str(text);
+    }
+
+    /// Drop the input before the current position, keeping reported spans unchanged.
+    /// Does nothing while tokens are pending, as their spans are relative to the current input.
+    fn discard_consumed(&mut self) {
+        if !self.pending.is_empty() || self.position == 0 {
+            return;
+        }
+        self.input.to_mut().drain(..self.position);
+        self.base += self.position;
+        self.position = 0;
+    }
+
+    /// Place a span produced from the current input in the full source.
+    fn locate(&self, span: &mut Span) {
+        span.start += self.base;
+        span.end += self.base;
     }
 
     /// Peek at a character in the input without advancing the position.
-    /// Takes an `offset` from the current position.
+    /// Takes an `offset` in characters from the current position.
     /// Returns `None` if the end of the input is reached.
     fn peek(&self, offset: usize) -> Option<char> {
-        self.input.chars().nth(self.position + offset)
+        self.input[self.position..].chars().nth(offset)
     }
 
     /// Advance the position in the input by `count` characters.
     fn advance(&mut self, count: usize) {
         assert!(count > 0, "Count must be greater than zero");
-        self.position += count;
-        // Ensure the position does not exceed the length of the input
-        if self.position > self.input.len() {
-            self.position = self.input.len();
-        }
+        let rest = &self.input[self.position..];
+        // Ensure the position does not go past the end of the input
+        self.position += rest
+            .char_indices()
+            .nth(count)
+            .map_or(rest.len(), |(i, _)| i);
     }
 
     /// Get the current character in the input.
@@ -55,115 +333,616 @@ impl Lexer {
     /// Consumes `count` characters and returns the specified TokenKind.
     fn consume(&mut self, kind: tokens::TokenKind, count: usize) -> Token {
         assert!(count > 0, "Count must be greater than zero");
-        assert!(
-            self.position + count <= self.input.len(),
-            "Count exceeds input length"
-        );
+        assert!(self.peek(count - 1).is_some(), "Count exceeds input length");
         let start = self.position;
         self.advance(count);
-        Token::new(kind, start, count)
+        Token::new(kind, start, self.position - start)
     }
 
-    /// Reads a keyword from the input.
-    fn read_keyword(&mut self) -> String {
-        let start = self.position;
+    /// Advances past characters in the input while they match `predicate`.
+    fn skip_while(&mut self, predicate: impl Fn(char) -> bool) {
+        while self.peek(0).is_some_and(&predicate) {
+            self.advance(1);
+        }
+    }
+
+    /// Reads characters from the input while they match `predicate`.
+    fn read_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut text = String::new();
         while let Some(ch) = self.peek(0) {
-            if matches!(ch, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_') {
+            if predicate(ch) {
+                text.push(ch);
                 self.advance(1);
             } else {
                 break;
             }
         }
-        self.input[start..self.position].to_string()
+        text
+    }
+
+    /// Reads characters from the input onto the end of `text` while they match `predicate`.
+    /// Returns an error if there are more than the maximum token length,
+    /// after skipping the rest of them without storing them.
+    fn read_limited(
+        &mut self,
+        text: &mut String,
+        predicate: impl Fn(char) -> bool,
+    ) -> Result<(), LexError> {
+        let start = self.position;
+        while let Some(ch) = self.peek(0).filter(|&ch| predicate(ch)) {
+            if self
+                .options
+                .max_token_len
+                .is_some_and(|max| text.len() >= max)
+            {
+                self.skip_while(&predicate);
+                return Err(LexError::TokenTooLong {
+                    span: Span::new(start, self.position),
+                });
+            }
+            text.push(ch);
+            self.advance(1);
+        }
+        Ok(())
+    }
+
+    /// Reads a keyword from the input into the `word` buffer.
+    /// Returns an error if it is longer than the maximum token length.
+    fn read_keyword(&mut self) -> Result<(), LexError> {
+        let mut word = std::mem::take(&mut self.word);
+        word.clear();
+        let result = self.read_limited(
+            &mut word,
+            |ch| matches!(ch, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_'),
+        );
+        self.word = word;
+        result
+    }
+
+    /// Reads an integer literal from the input.
+    /// Returns an error if the integer does not fit in an `isize`, unless big integers are enabled,
+    /// or if it is longer than the maximum token length.
+    fn read_integer(&mut self) -> Result<tokens::TokenKind, LexError> {
+        let start = self.position;
+        let mut digits = String::new();
+        self.read_limited(&mut digits, |ch| ch.is_ascii_digit())?;
+        // The digits are all ASCII, so parsing can only fail on overflow
+        match digits.parse() {
+            Ok(value) => Ok(tokens::TokenKind::IntLiteral(value)),
+            Err(_) if self.options.big_integers => Ok(tokens::TokenKind::BigIntLiteral(digits)),
+            Err(_) => Err(LexError::IntOverflow {
+                span: Span::new(start, self.position),
+            }),
+        }
+    }
+
+    /// Reads a line comment up to, but not including, its line terminator.
+    /// Both `\n` and `\r\n` end the comment, so a carriage return is never part of it.
+    /// Returns the span of the comment, including the leading `//`.
+    fn read_line_comment(&mut self) -> Span {
+        let start = self.position;
+        while let Some(ch) = self.peek(0) {
+            if ch == '\n' || (ch == '\r' && self.peek(1) == Some('\n')) {
+                break;
+            }
+            self.advance(1);
+        }
+        Span::new(start, self.position)
+    }
+
+    /// Checks if `ch` is whitespace that does not produce a token of its own.
+    /// Newlines outside any parentheses, brackets or braces are tokens in indentation mode,
+    /// and in newline mode when they end a line holding tokens.
+    fn is_insignificant_whitespace(&self, ch: char) -> bool {
+        if ch != '\n' {
+            return is_whitespace(ch);
+        }
+        if self.depth > 0 {
+            return true;
+        }
+        if self.options.indentation {
+            return false;
+        }
+        !self.options.newlines || self.at_line_start
+    }
+
+    /// Checks if the input is at a doc comment: exactly three slashes.
+    /// Four or more slashes start an ordinary comment, as in Rust.
+    fn at_doc_comment(&self) -> bool {
+        self.peek(0) == Some('/')
+            && self.peek(1) == Some('/')
+            && self.peek(2) == Some('/')
+            && self.peek(3) != Some('/')
+    }
+
+    /// Reads a `///` doc comment, returning a token holding the text after the slashes.
+    fn read_doc_comment(&mut self) -> Token {
+        let span = self.read_line_comment();
+        let text = self.input[span.start + 3..span.end].to_string();
+        Token::new(tokens::TokenKind::DocComment(text), span.start, span.len())
+    }
+
+    /// Reads an ordinary `//` comment, returning a token holding the text after the slashes.
+    fn read_comment(&mut self) -> Token {
+        let span = self.read_line_comment();
+        let text = self.input[span.start + 2..span.end].to_string();
+        Token::new(tokens::TokenKind::Comment(text), span.start, span.len())
+    }
+
+    /// Reads a string literal, starting at its opening quote.
+    /// Supports the escapes `\n`, `\r`, `\t`, `\0`, `\\`, `\"` and `\'`,
+    /// along with `\xNN` for a character up to `U+00FF` and `\u{...}` for any Unicode scalar value.
+    /// Errors for invalid escapes or a missing closing quote are queued rather than returned,
+    /// so a recovering caller still receives a best-effort `StringLiteral`.
+    fn read_string(&mut self) -> Token {
+        let start = self.position;
+        self.advance(1);
+        let mut value = String::new();
+        loop {
+            match self.peek(0) {
+                None => {
+                    self.pending.push_back(Err(LexError::UnterminatedString {
+                        span: Span::new(start, self.position),
+                    }));
+                    break;
+                }
+                Some('"') => {
+                    self.advance(1);
+                    break;
+                }
+                Some('\\') => match self.read_escape() {
+                    Ok(ch) => value.push(ch),
+                    Err(error) => self.pending.push_back(Err(error)),
+                },
+                Some(ch) => {
+                    value.push(ch);
+                    self.advance(1);
+                }
+            }
+        }
+
+        let kind = tokens::TokenKind::StringLiteral(value);
+        Token::new(kind, start, self.position - start)
+    }
+
+    /// Reads a character literal, starting at its opening quote.
+    /// Supports the same escapes as string literals.
+    /// Errors for invalid escapes, or a literal that does not hold exactly one character, are queued
+    /// rather than returned, so a recovering caller still receives a best-effort `CharLiteral`.
+    fn read_char(&mut self) -> Token {
+        let start = self.position;
+        self.advance(1);
+        let mut value = None;
+        let mut count = 0;
+        let mut closed = false;
+        // A literal missing its closing quote ends at the end of the line
+        while let Some(ch) = self.peek(0) {
+            match ch {
+                '\n' => break,
+                '\'' => {
+                    self.advance(1);
+                    closed = true;
+                    break;
+                }
+                '\\' => match self.read_escape() {
+                    Ok(ch) => {
+                        value.get_or_insert(ch);
+                        count += 1;
+                    }
+                    Err(error) => {
+                        self.pending.push_back(Err(error));
+                        count += 1;
+                    }
+                },
+                ch => {
+                    value.get_or_insert(ch);
+                    count += 1;
+                    self.advance(1);
+                }
+            }
+        }
+        if !closed || count != 1 {
+            self.pending.push_back(Err(LexError::InvalidCharLiteral {
+                span: Span::new(start, self.position),
+            }));
+        }
+
+        let value = value.unwrap_or(char::REPLACEMENT_CHARACTER);
+        Token::new(
+            tokens::TokenKind::CharLiteral(value),
+            start,
+            self.position - start,
+        )
     }
 
-    /// Reads an integer from the input.
-    fn read_integer(&mut self) -> isize {
+    /// Reads an escape sequence, starting at its backslash, and returns the character it stands for.
+    fn read_escape(&mut self) -> Result<char, LexError> {
         let start = self.position;
-        while let Some(char) = self.peek(0) {
-            if char.is_ascii_digit() {
+        self.advance(1);
+        let ch = match self.peek(0) {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some('x') => return self.read_hex_escape(start),
+            Some('u') => return self.read_unicode_escape(start),
+            // Leave the end of input for the caller to report
+            None => {
+                return Err(LexError::InvalidEscape {
+                    span: Span::new(start, self.position),
+                });
+            }
+            Some(_) => {
+                self.advance(1);
+                return Err(LexError::InvalidEscape {
+                    span: Span::new(start, self.position),
+                });
+            }
+        };
+        self.advance(1);
+        Ok(ch)
+    }
+
+    /// Reads the rest of a `\xNN` escape, starting at the `x`.
+    /// Exactly two hex digits are required, and stand for the character `U+0000` to `U+00FF`.
+    fn read_hex_escape(&mut self, start: usize) -> Result<char, LexError> {
+        self.advance(1);
+        let mut value = 0;
+        for _ in 0..2 {
+            match self.peek(0).and_then(|ch| ch.to_digit(16)) {
+                Some(digit) => {
+                    value = value * 16 + digit;
+                    self.advance(1);
+                }
+                None => {
+                    return Err(LexError::InvalidEscape {
+                        span: Span::new(start, self.position),
+                    });
+                }
+            }
+        }
+        // Two hex digits always fit in a byte
+        Ok(char::from(value as u8))
+    }
+
+    /// Reads the rest of a `\u{...}` escape, starting at the `u`.
+    /// The braces must hold one to six hex digits naming a Unicode scalar value,
+    /// so surrogates and anything above `U+10FFFF` are rejected.
+    fn read_unicode_escape(&mut self, start: usize) -> Result<char, LexError> {
+        self.advance(1);
+        let invalid = |lexer: &Self| LexError::InvalidEscape {
+            span: Span::new(start, lexer.position),
+        };
+        if self.peek(0) != Some('{') {
+            return Err(invalid(self));
+        }
+        self.advance(1);
+
+        let mut value: u32 = 0;
+        let mut digits = 0;
+        while let Some(digit) = self.peek(0).and_then(|ch| ch.to_digit(16)) {
+            // Only the first six digits can be valid, so stop accumulating before overflow
+            if digits < 6 {
+                value = value * 16 + digit;
+            }
+            digits += 1;
+            self.advance(1);
+        }
+        if self.peek(0) != Some('}') {
+            return Err(invalid(self));
+        }
+        self.advance(1);
+
+        if !(1..=6).contains(&digits) {
+            return Err(invalid(self));
+        }
+        char::from_u32(value).ok_or_else(|| invalid(self))
+    }
+
+    /// Gets the number of characters in a line continuation at the current position:
+    /// a backslash directly followed by a line terminator. Returns zero if there is none.
+    fn continuation_len(&self) -> usize {
+        match (self.peek(0), self.peek(1), self.peek(2)) {
+            (Some('\\'), Some('\n'), _) => 2,
+            (Some('\\'), Some('\r'), Some('\n')) => 3,
+            _ => 0,
+        }
+    }
+
+    /// Advances past a run of insignificant whitespace and line continuations.
+    fn skip_whitespace(&mut self) {
+        while let Some(ch) = self.peek(0) {
+            let len = self.continuation_len();
+            if len > 0 {
+                self.advance(len);
+            } else if self.is_insignificant_whitespace(ch) {
                 self.advance(1);
             } else {
                 break;
             }
         }
-        self.input[start..self.position].parse().unwrap()
+    }
+
+    /// Skips whitespace, line continuations and line comments before the next token.
+    /// Whitespace and comments are left in place when they are emitted as tokens,
+    /// and doc comments are always tokens.
+    /// A continuation joins two lines into one logical line, so no `Newline` is produced for it.
+    fn skip_trivia(&mut self) {
+        while let Some(ch) = self.peek(0) {
+            if !self.options.emit_whitespace
+                && (self.is_insignificant_whitespace(ch) || self.continuation_len() > 0)
+            {
+                self.skip_whitespace();
+            } else if ch == '/'
+                && self.peek(1) == Some('/')
+                && !self.at_doc_comment()
+                && !self.options.emit_comments
+            {
+                self.read_line_comment();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Reads the indentation at the start of a logical line, skipping blank and comment-only lines.
+    /// Queues an `Indent` if the line opens a block, or a `Dedent` for each block it closes.
+    /// Returns an error if the indentation mixes tabs and spaces inconsistently with the open
+    /// blocks, or closes blocks to a level that was never opened.
+    fn read_indentation(&mut self) -> Result<(), LexError> {
+        use tokens::TokenKind::{Dedent, Indent};
+
+        let (start, indent) = loop {
+            let start = self.position;
+            let indent = self.read_while(|ch| ch == ' ' || ch == '\t');
+            match (self.peek(0), self.peek(1)) {
+                // Leave the end of input to `next_token`, which closes any open blocks
+                (None, _) => return Ok(()),
+                (Some('\n'), _) => self.advance(1),
+                (Some('\r'), Some('\n')) => self.advance(2),
+                (Some('/'), Some('/')) if !self.at_doc_comment() => {
+                    self.read_line_comment();
+                }
+                _ => break (start, indent),
+            }
+        };
+        self.at_line_start = false;
+
+        let span = Span::new(start, self.position);
+        let current = self.indents.last().map_or("", String::as_str);
+        if indent == current {
+            Ok(())
+        } else if indent.starts_with(current) {
+            self.pending
+                .push_back(Ok(Token::new(Indent, span.start, span.len())));
+            self.indents.push(indent);
+            Ok(())
+        } else if current.starts_with(&indent) {
+            while self
+                .indents
+                .last()
+                .is_some_and(|top| top.len() > indent.len())
+            {
+                self.indents.pop();
+                self.pending
+                    .push_back(Ok(Token::new(Dedent, self.position, 0)));
+            }
+            // The line must return to the indentation of an enclosing block
+            if self.indents.last().map_or("", String::as_str) == indent {
+                Ok(())
+            } else {
+                Err(LexError::InconsistentIndent { span })
+            }
+        } else {
+            Err(LexError::InconsistentIndent { span })
+        }
+    }
+
+    /// Produces the tokens closing the input in indentation mode:
+    /// a `Newline` ending the last line, then a `Dedent` for each open block.
+    /// Returns `None` once everything is closed.
+    fn close_lines(&mut self) -> Option<Token> {
+        use tokens::TokenKind::{Dedent, Newline};
+
+        if !self.at_line_start {
+            self.at_line_start = true;
+            Some(Token::new(Newline, self.position, 0))
+        } else {
+            self.indents.pop()?;
+            Some(Token::new(Dedent, self.position, 0))
+        }
+    }
+
+    /// Consumes the unexpected character `ch` at the current position and builds an error for it.
+    /// Consuming it means lexing can resume after the error.
+    fn unexpected(&mut self, ch: char) -> LexError {
+        let start = self.position;
+        self.advance(1);
+        LexError::UnexpectedChar {
+            ch,
+            span: Span::new(start, self.position),
+        }
+    }
+
+    /// Consumes the character `ch` that was not followed by the rest of any `expected` operator,
+    /// and builds an error listing them. Consuming it means lexing can resume after the error.
+    fn expected_one_of(&mut self, ch: char, expected: &'static [&'static str]) -> LexError {
+        let start = self.position;
+        self.advance(1);
+        LexError::ExpectedOneOf {
+            ch,
+            expected,
+            span: Span::new(start, self.position),
+        }
     }
 
     /// Reads from the input and produces a token.
-    pub fn next_token(&mut self) -> Token {
+    /// Returns an error if the input does not form a valid token.
+    /// The offending input is consumed, so lexing can continue after an error.
+    pub fn next_token(&mut self) -> Result<Token, LexError> {
+        let result = self.lex_token();
+        // In newline mode, anything but trivia means the line holds a statement to terminate
+        let trivia = matches!(&result, Ok(token) if token.kind == tokens::TokenKind::Newline
+            || token.kind.category() == tokens::TokenCategory::Trivia);
+        if self.options.newlines && !self.options.indentation && !trivia {
+            self.at_line_start = false;
+        }
+        match result {
+            Ok(mut token) => {
+                self.locate(&mut token.pos);
+                Ok(token)
+            }
+            Err(mut error) => {
+                self.locate(error.span_mut());
+                Err(error)
+            }
+        }
+    }
+
+    /// Returns the first error queued while reading a literal, queueing the literal after the errors.
+    /// Returns the literal itself if it was read without errors.
+    fn report_literal(&mut self, token: Token) -> Result<Token, LexError> {
+        match self.pending.pop_front() {
+            Some(error) => {
+                self.pending.push_back(Ok(token));
+                error
+            }
+            None => Ok(token),
+        }
+    }
+
+    /// Produces the next token, with spans relative to the current input.
+    fn lex_token(&mut self) -> Result<Token, LexError> {
         use tokens::TokenKind::*;
 
+        // A byte order mark at the very start of the input is an encoding detail, not source
+        if self.position == 0 && self.base == 0 && self.peek(0) == Some(BYTE_ORDER_MARK) {
+            self.advance(1);
+            // Emitted whitespace covers everything between tokens, so the mark joins the run
+            // of whitespace after it, unless that is the first line's indentation
+            if self.options.emit_whitespace {
+                if !self.options.indentation {
+                    self.skip_whitespace();
+                }
+                return Ok(Token::new(Whitespace, 0, self.position));
+            }
+        }
+
+        if self.options.indentation && self.at_line_start && self.pending.is_empty() {
+            self.read_indentation()?;
+        }
+        if let Some(result) = self.pending.pop_front() {
+            return result;
+        }
+
+        self.skip_trivia();
         let Some(ch) = self.peek(0) else {
-            return Token::new(tokens::TokenKind::Eof, self.position, 0);
+            if self.options.indentation
+                && let Some(token) = self.close_lines()
+            {
+                return Ok(token);
+            }
+            return Ok(Token::new(tokens::TokenKind::Eof, self.position, 0));
         };
-        match ch {
-            ch if ch.is_whitespace() => {
-                self.advance(1);
-                self.next_token()
-            }
-            '(' => self.consume(LParen, 1),
-            ')' => self.consume(RParen, 1),
-            '[' => self.consume(LBracket, 1),
-            ']' => self.consume(RBracket, 1),
-            '{' => self.consume(LBrace, 1),
-            '}' => self.consume(RBrace, 1),
+        let token = match ch {
+            '\n' if self.options.indentation && self.depth == 0 => {
+                self.at_line_start = true;
+                self.consume(Newline, 1)
+            }
+            '\n' if self.options.newlines && !self.is_insignificant_whitespace(ch) => {
+                self.at_line_start = true;
+                self.consume(Newline, 1)
+            }
+            // Line continuations are part of the whitespace around them
+            ch if is_whitespace(ch) || self.continuation_len() > 0 => {
+                let start = self.position;
+                self.skip_whitespace();
+                Token::new(Whitespace, start, self.position - start)
+            }
+            '(' => {
+                self.depth += 1;
+                self.consume(LParen, 1)
+            }
+            ')' => {
+                self.depth = self.depth.saturating_sub(1);
+                self.consume(RParen, 1)
+            }
+            '[' => {
+                self.depth += 1;
+                self.consume(LBracket, 1)
+            }
+            ']' => {
+                self.depth = self.depth.saturating_sub(1);
+                self.consume(RBracket, 1)
+            }
+            '{' => {
+                self.depth += 1;
+                self.consume(LBrace, 1)
+            }
+            '}' => {
+                self.depth = self.depth.saturating_sub(1);
+                self.consume(RBrace, 1)
+            }
             ',' => self.consume(Comma, 1),
             ':' => self.consume(Colon, 1),
-            '=' => {
-                if self.peek(1) == Some('=') {
-                    self.consume(Equals, 2)
-                } else {
-                    self.consume(Assign, 1)
-                }
-            }
+            '@' => self.consume(At, 1),
+            // There is no `??`, so `a??` applies `?` twice
+            '?' => self.consume(Question, 1),
+            '=' => match self.peek(1) {
+                Some('>') => self.consume(FatArrow, 2),
+                Some('=') => self.consume(Equals, 2),
+                _ => self.consume(Assign, 1),
+            },
             '+' => self.consume(Plus, 1),
             '-' => self.consume(Minus, 1),
             '*' => self.consume(Multiply, 1),
-            '/' => {
-                if self.peek(1) == Some('/') {
-                    // Consume the comment
-                    while self.peek(0) != Some('\n') && self.peek(0).is_some() {
-                        self.advance(1);
-                    }
-                    self.next_token()
-                } else {
-                    self.consume(Divide, 1)
-                }
+            '^' => self.consume(Exponent, 1),
+            '~' => self.consume(BitXor, 1),
+            '/' if self.at_doc_comment() => self.read_doc_comment(),
+            // Ordinary comments are skipped as trivia unless they are emitted
+            '/' if self.peek(1) == Some('/') => self.read_comment(),
+            '/' => self.consume(Divide, 1),
+            '"' => {
+                let token = self.read_string();
+                self.report_literal(token)?
             }
-            '%' => self.consume(Modulus, 1),
-            '<' => {
-                if self.peek(1) == Some('=') {
-                    self.consume(LessThanOrEqual, 2)
-                } else {
-                    self.consume(LessThan, 1)
-                }
+            '\'' => {
+                let token = self.read_char();
+                self.report_literal(token)?
             }
-            '>' => {
+            '%' => {
                 if self.peek(1) == Some('=') {
-                    self.consume(GreaterThanOrEqual, 2)
+                    self.consume(ModAssign, 2)
                 } else {
-                    self.consume(GreaterThan, 1)
+                    self.consume(Modulus, 1)
                 }
             }
+            '<' => match self.peek(1) {
+                Some('=') => self.consume(LessThanOrEqual, 2),
+                Some('<') => self.consume(ShiftLeft, 2),
+                _ => self.consume(LessThan, 1),
+            },
+            // Closing nested type arguments also gives `>>`, which the parser splits when needed
+            '>' => match self.peek(1) {
+                Some('=') => self.consume(GreaterThanOrEqual, 2),
+                Some('>') => self.consume(ShiftRight, 2),
+                _ => self.consume(GreaterThan, 1),
+            },
             '&' => {
                 if self.peek(1) == Some('&') {
                     self.consume(And, 2)
                 } else {
-                    panic!(
-                        "Unexpected character: '{}' at position: {}",
-                        ch, self.position
-                    );
+                    return Err(self.expected_one_of(ch, &["&&"]));
                 }
             }
             '|' => {
                 if self.peek(1) == Some('|') {
                     self.consume(Or, 2)
                 } else {
-                    panic!(
-                        "Unexpected character: '{}' at position: {}",
-                        ch, self.position
-                    );
+                    return Err(self.expected_one_of(ch, &["||"]));
                 }
             }
             '!' => {
@@ -173,33 +952,136 @@ impl Lexer {
                     self.consume(Not, 1)
                 }
             }
-            'a'..='z' | 'A'..='Z' => {
+            'a'..='z' | 'A'..='Z' | '_' => {
                 let start = self.position;
-                let kw = self.read_keyword();
+                self.read_keyword()?;
+                let kw = &self.word;
+                // The span covers what was read, whatever the encoded length of the word
+                let len = self.position - start;
 
-                // Look up the keyword in the map, return identifier if not found
-                if let Some(kind) = KEYWORDS.get(kw.as_str()) {
-                    Token::new(kind.clone(), start, kw.len())
+                // Look up the keyword, return identifier if not found
+                if let Some(keyword) = tokens::keyword_from_str(kw) {
+                    Token::new(keyword.into(), start, len)
+                } else if kw == "_" {
+                    Token::new(Underscore, start, len)
+                } else if self.extra_keywords.contains(kw) {
+                    Token::new(CustomKeyword(kw.clone()), start, len)
+                } else if self.options.intern_identifiers {
+                    let symbol = self.interner.intern(kw);
+                    Token::new(InternedIdentifier(symbol), start, len)
                 } else {
-                    Token::new(Identifier(kw.clone()), start, kw.len())
+                    Token::new(Identifier(kw.clone()), start, len)
                 }
             }
             '0'..='9' => {
                 let start = self.position;
-                let value = self.read_integer();
-                Token::new(IntLiteral(value), start, self.position - start)
+                let kind = self.read_integer()?;
+                Token::new(kind, start, self.position - start)
             }
 
-            ch => {
-                panic!(
-                    "Unexpected character: '{}' at position: {}",
-                    ch, self.position
-                );
+            ch => return Err(self.unexpected(ch)),
+        };
+        Ok(token)
+    }
+}
+
+/// Checks if `ch` is whitespace separating tokens.
+/// Among ASCII characters only spaces, tabs and line terminators count, so other control characters
+/// such as form feeds are reported as unexpected rather than silently skipped.
+fn is_whitespace(ch: char) -> bool {
+    matches!(ch, ' ' | '\t' | '\n' | '\r') || (!ch.is_ascii() && ch.is_whitespace())
+}
+
+/// Guesses how many tokens the input will produce, to preallocate the token vector.
+/// Typical source averages a few characters per token, including the whitespace between them.
+fn estimate_token_count(input: &str) -> usize {
+    input.len() / 4 + 1
+}
+
+/// Reads every token up to and including `Eof` from the lexer, stopping at the first error.
+fn collect_tokens(lexer: &mut Lexer) -> Result<Vec<Token>, LexError> {
+    let mut tokens = Vec::with_capacity(estimate_token_count(lexer.rest()));
+    loop {
+        let token = lexer.next_token()?;
+        let done = token.kind == tokens::TokenKind::Eof;
+        tokens.push(token);
+        if done {
+            return Ok(tokens);
+        }
+    }
+}
+
+/// Lexes the whole input, returning every token up to and including `Eof`.
+/// Stops at the first error.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, LexError> {
+    let mut lexer = Lexer::from_str(input);
+    collect_tokens(&mut lexer)
+}
+
+/// Lexes the whole input keeping whitespace and comments as tokens, stopping at the first error.
+/// When lexing succeeds, the spans of the tokens cover the input without gaps or overlaps,
+/// so [`stream::reconstruct`] gives back the original source.
+pub fn tokenize_with_trivia(input: &str) -> Result<Vec<Token>, LexError> {
+    let options = LexerOptions {
+        emit_whitespace: true,
+        emit_comments: true,
+        ..LexerOptions::default()
+    };
+    let mut lexer = Lexer::from_str(input).with_options(options);
+    collect_tokens(&mut lexer)
+}
+
+/// Lexes the whole input with identifiers interned, stopping at the first error.
+/// Returns every token up to and including `Eof`, along with the interner resolving their names.
+pub fn tokenize_interned(input: &str) -> Result<(Vec<Token>, Interner), LexError> {
+    let options = LexerOptions {
+        intern_identifiers: true,
+        ..LexerOptions::default()
+    };
+    let mut lexer = Lexer::from_str(input).with_options(options);
+    let tokens = collect_tokens(&mut lexer)?;
+    Ok((tokens, lexer.into_interner()))
+}
+
+/// Lexes the whole input, recovering from errors instead of stopping at the first one.
+/// Returns every token up to and including `Eof`, along with the errors encountered.
+pub fn tokenize_all(input: &str) -> (Vec<Token>, Vec<LexError>) {
+    let mut lexer = Lexer::from_str(input);
+    let mut tokens = Vec::with_capacity(estimate_token_count(input));
+    let mut errors = Vec::new();
+    loop {
+        match lexer.next_token() {
+            Ok(token) => {
+                let done = token.kind == tokens::TokenKind::Eof;
+                tokens.push(token);
+                if done {
+                    return (tokens, errors);
+                }
             }
+            Err(error) => errors.push(error),
         }
     }
 }
 
+/// Lexes an input holding exactly one token, ignoring whitespace and comments around it.
+/// Returns `Eof` for an input with no tokens, and an error if anything follows the token.
+pub fn lex_one(input: &str) -> Result<Token, LexError> {
+    let mut lexer = Lexer::from_str(input);
+    let token = lexer.next_token()?;
+    if token.is_eof() {
+        return Ok(token);
+    }
+    // Whatever follows is reported as a whole, even if it would not lex
+    let trailing = match lexer.next_token() {
+        Ok(next) if next.is_eof() => return Ok(token),
+        Ok(next) => next.pos.start,
+        Err(error) => error.span().start,
+    };
+    Err(LexError::TrailingInput {
+        span: Span::new(trailing, input.len()),
+    })
+}
+
 #[cfg(test)]
 mod lexer {
     use tokens::TokenKind;
@@ -207,7 +1089,7 @@ mod lexer {
     use super::*;
 
     fn expect_token(lexer: &mut Lexer, expected: TokenKind) {
-        let token = lexer.next_token();
+        let token = lexer.next_token().unwrap();
         assert_eq!(
             token.kind, expected,
             "Expected token: {:?}, but got: {:?}",
@@ -237,7 +1119,7 @@ mod lexer {
         expect_token(&mut lexer, Eof);
 
         // Check that the lexer has reached the end of the input
-        assert_eq!(lexer.position, input.len());
+        assert_eq!(lexer.offset(), input.len());
     }
 
     #[test]
@@ -258,7 +1140,7 @@ mod lexer {
         expect_token(&mut lexer, Eof);
 
         // Check that the lexer has reached the end of the input
-        assert_eq!(lexer.position, input.len());
+        assert_eq!(lexer.offset(), input.len());
     }
 
     #[test]
@@ -283,16 +1165,166 @@ mod lexer {
     }
 
     #[test]
-    fn parse_comparison_operators() {
+    fn parse_modulus_and_mod_assign() {
         use tokens::TokenKind::*;
 
-        let input = "a < b > c <= d >= e == f != g";
-        let mut lexer = Lexer::new(input.to_string());
+        let a = || Identifier("a".to_string());
+        let b = || Identifier("b".to_string());
+        for (input, expected) in [
+            ("a % b", vec![a(), Modulus, b(), Eof]),
+            ("a %= b", vec![a(), ModAssign, b(), Eof]),
+            ("a % = b", vec![a(), Modulus, Assign, b(), Eof]),
+        ] {
+            assert_eq!(
+                stream::kinds(&tokenize(input).unwrap()),
+                expected,
+                "{}",
+                input
+            );
+        }
+        assert_eq!(lex_one("%="), Ok(Token::new(ModAssign, 0, 2)));
+    }
 
-        expect_token(&mut lexer, Identifier("a".to_string()));
-        expect_token(&mut lexer, LessThan);
-        expect_token(&mut lexer, Identifier("b".to_string()));
-        expect_token(&mut lexer, GreaterThan);
+    #[test]
+    fn parse_attribute() {
+        use tokens::TokenKind::*;
+
+        let mut lexer = Lexer::from_str("@inline fn f()");
+
+        assert_eq!(lexer.next_token(), Ok(Token::new(At, 0, 1)));
+        assert_eq!(
+            lexer.next_token(),
+            Ok(Token::new(Identifier("inline".to_string()), 1, 6))
+        );
+        expect_token(&mut lexer, Fn);
+        expect_token(&mut lexer, Identifier("f".to_string()));
+        expect_token(&mut lexer, LParen);
+        expect_token(&mut lexer, RParen);
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_fat_arrow() {
+        use tokens::TokenKind::*;
+
+        let a = || Identifier("a".to_string());
+        let b = || Identifier("b".to_string());
+        for (input, expected) in [
+            ("a => b", vec![a(), FatArrow, b(), Eof]),
+            ("a == b", vec![a(), Equals, b(), Eof]),
+            ("a = b", vec![a(), Assign, b(), Eof]),
+            ("a = > b", vec![a(), Assign, GreaterThan, b(), Eof]),
+            ("a ==> b", vec![a(), Equals, GreaterThan, b(), Eof]),
+            ("a =>= b", vec![a(), FatArrow, Assign, b(), Eof]),
+        ] {
+            assert_eq!(
+                stream::kinds(&tokenize(input).unwrap()),
+                expected,
+                "{}",
+                input
+            );
+        }
+        assert_eq!(lex_one("=>"), Ok(Token::new(FatArrow, 0, 2)));
+        assert_eq!(FatArrow.to_string(), "=>");
+    }
+
+    #[test]
+    fn parse_question() {
+        use tokens::TokenKind::*;
+
+        assert_eq!(lex_one("?"), Ok(Token::new(Question, 0, 1)));
+        stream::assert_token_kinds(
+            &tokenize("read(f)?? + x?").unwrap(),
+            &[
+                Identifier("read".to_string()),
+                LParen,
+                Identifier("f".to_string()),
+                RParen,
+                Question,
+                Question,
+                Plus,
+                Identifier("x".to_string()),
+                Question,
+                Eof,
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_shifts() {
+        use tokens::TokenKind::*;
+
+        let a = || Identifier("a".to_string());
+        let b = || Identifier("b".to_string());
+        for (input, expected) in [
+            ("a >> b", vec![a(), ShiftRight, b(), Eof]),
+            ("a << b", vec![a(), ShiftLeft, b(), Eof]),
+            ("a > > b", vec![a(), GreaterThan, GreaterThan, b(), Eof]),
+            ("a <<= b", vec![a(), ShiftLeft, Assign, b(), Eof]),
+            ("a >>> b", vec![a(), ShiftRight, GreaterThan, b(), Eof]),
+        ] {
+            assert_eq!(
+                stream::kinds(&tokenize(input).unwrap()),
+                expected,
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn parse_negation_is_two_tokens() {
+        use tokens::TokenKind::*;
+
+        for (input, expected) in [
+            ("-5", vec![Minus, IntLiteral(5)]),
+            (
+                "a-5",
+                vec![Identifier("a".to_string()), Minus, IntLiteral(5)],
+            ),
+            ("(-5)", vec![LParen, Minus, IntLiteral(5), RParen]),
+            ("--5", vec![Minus, Minus, IntLiteral(5)]),
+        ] {
+            let kinds: Vec<_> = tokenize(input)
+                .unwrap()
+                .into_iter()
+                .map(|token| token.kind)
+                .filter(|kind| *kind != Eof)
+                .collect();
+            assert_eq!(kinds, expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn parse_exponent_and_xor() {
+        use tokens::TokenKind::*;
+
+        let input = "2 ^ 3 ~ a^b~c";
+        let mut lexer = Lexer::from_str(input);
+
+        expect_token(&mut lexer, IntLiteral(2));
+        expect_token(&mut lexer, Exponent);
+        expect_token(&mut lexer, IntLiteral(3));
+        expect_token(&mut lexer, BitXor);
+        expect_token(&mut lexer, Identifier("a".to_string()));
+        expect_token(&mut lexer, Exponent);
+        expect_token(&mut lexer, Identifier("b".to_string()));
+        expect_token(&mut lexer, BitXor);
+        expect_token(&mut lexer, Identifier("c".to_string()));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_comparison_operators() {
+        use tokens::TokenKind::*;
+
+        let input = "a < b > c <= d >= e == f != g";
+        let mut lexer = Lexer::new(input.to_string());
+
+        expect_token(&mut lexer, Identifier("a".to_string()));
+        expect_token(&mut lexer, LessThan);
+        expect_token(&mut lexer, Identifier("b".to_string()));
+        expect_token(&mut lexer, GreaterThan);
         expect_token(&mut lexer, Identifier("c".to_string()));
         expect_token(&mut lexer, LessThanOrEqual);
         expect_token(&mut lexer, Identifier("d".to_string()));
@@ -321,6 +1353,68 @@ mod lexer {
         expect_token(&mut lexer, Eof);
     }
 
+    #[test]
+    fn parse_lone_and_or() {
+        use tokens::TokenKind::*;
+
+        for (input, ch, expected) in [("a & b", '&', &["&&"]), ("a | b", '|', &["||"])] {
+            let mut lexer = Lexer::from_str(input);
+
+            expect_token(&mut lexer, Identifier("a".to_string()));
+            assert_eq!(
+                lexer.next_token(),
+                Err(LexError::ExpectedOneOf {
+                    ch,
+                    expected,
+                    span: Span::new(2, 3),
+                })
+            );
+            expect_token(&mut lexer, Identifier("b".to_string()));
+            expect_token(&mut lexer, Eof);
+        }
+    }
+
+    #[test]
+    fn parse_not() {
+        use tokens::TokenKind::*;
+
+        let mut lexer = Lexer::from_str("!x");
+        assert_eq!(lexer.next_token(), Ok(Token::new(Not, 0, 1)));
+        expect_token(&mut lexer, Identifier("x".to_string()));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_lone_not() {
+        use tokens::TokenKind::*;
+
+        let mut lexer = Lexer::from_str("!");
+        assert_eq!(lexer.next_token(), Ok(Token::new(Not, 0, 1)));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_trailing_not() {
+        use tokens::TokenKind::*;
+
+        // `!` as the last character must not look past the end for `=`
+        let mut lexer = Lexer::from_str("x !");
+        expect_token(&mut lexer, Identifier("x".to_string()));
+        assert_eq!(lexer.next_token(), Ok(Token::new(Not, 2, 1)));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_spaced_not_equals() {
+        use tokens::TokenKind::*;
+
+        let mut lexer = Lexer::from_str("! = !=");
+        expect_token(&mut lexer, Not);
+        expect_token(&mut lexer, Assign);
+        expect_token(&mut lexer, NotEquals);
+        expect_token(&mut lexer, Eof);
+    }
+
     #[test]
     fn parse_brackets_and_delimiters() {
         use tokens::TokenKind::*;
@@ -352,6 +1446,29 @@ mod lexer {
         expect_token(&mut lexer, Eof);
     }
 
+    #[test]
+    fn parse_all_keywords() {
+        for keyword in tokens::Keyword::ALL {
+            let mut lexer = Lexer::from_str(keyword.as_str());
+
+            expect_token(&mut lexer, keyword.into());
+            expect_token(&mut lexer, TokenKind::Eof);
+        }
+    }
+
+    #[test]
+    fn parse_keyword_near_misses() {
+        use tokens::TokenKind::*;
+
+        let input = "vals Val ifx els fnn elif_";
+        let mut lexer = Lexer::new(input.to_string());
+
+        for word in input.split(' ') {
+            expect_token(&mut lexer, Identifier(word.to_string()));
+        }
+        expect_token(&mut lexer, Eof);
+    }
+
     #[test]
     fn parse_identifiers() {
         use tokens::TokenKind::*;
@@ -367,6 +1484,20 @@ mod lexer {
         expect_token(&mut lexer, Eof);
     }
 
+    #[test]
+    fn parse_underscore() {
+        use tokens::TokenKind::*;
+
+        let input = "_ _x x_ __";
+        let mut lexer = Lexer::new(input.to_string());
+
+        expect_token(&mut lexer, Underscore);
+        expect_token(&mut lexer, Identifier("_x".to_string()));
+        expect_token(&mut lexer, Identifier("x_".to_string()));
+        expect_token(&mut lexer, Identifier("__".to_string()));
+        expect_token(&mut lexer, Eof);
+    }
+
     #[test]
     fn parse_function_declaration() {
         use tokens::TokenKind::*;
@@ -444,6 +1575,73 @@ mod lexer {
         expect_token(&mut lexer, Eof);
     }
 
+    #[test]
+    fn parse_crlf_comments() {
+        use tokens::TokenKind::*;
+
+        let input = "val x = 5 // first\r\n// second\r\nval y = 10";
+        let mut lexer = Lexer::new(input.to_string());
+
+        expect_token(&mut lexer, Val);
+        expect_token(&mut lexer, Identifier("x".to_string()));
+        expect_token(&mut lexer, Assign);
+        expect_token(&mut lexer, IntLiteral(5));
+        expect_token(&mut lexer, Val);
+        expect_token(&mut lexer, Identifier("y".to_string()));
+        expect_token(&mut lexer, Assign);
+        expect_token(&mut lexer, IntLiteral(10));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_crlf_comment_span() {
+        let input = "// comment\r\nx";
+        let mut lexer = Lexer::new(input.to_string());
+
+        let span = lexer.read_line_comment();
+        assert_eq!(&input[span.start..span.end], "// comment");
+        assert_eq!(lexer.peek(0), Some('\r'));
+    }
+
+    #[test]
+    fn parse_doc_comments() {
+        use tokens::TokenKind::*;
+
+        let input = "// plain\n/// Adds two numbers.\n///\r\n//// not docs\nfn";
+        let mut lexer = Lexer::from_str(input);
+
+        assert_eq!(
+            lexer.next_token(),
+            Ok(Token::new(
+                DocComment(" Adds two numbers.".to_string()),
+                9,
+                21
+            ))
+        );
+        // Consecutive doc lines are separate tokens, without the carriage return
+        assert_eq!(
+            lexer.next_token(),
+            Ok(Token::new(DocComment(String::new()), 31, 3))
+        );
+        expect_token(&mut lexer, Fn);
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_doc_comment_after_code() {
+        use tokens::TokenKind::*;
+
+        let input = "a /// trailing\nb / c";
+        let mut lexer = Lexer::from_str(input);
+
+        expect_token(&mut lexer, Identifier("a".to_string()));
+        expect_token(&mut lexer, DocComment(" trailing".to_string()));
+        expect_token(&mut lexer, Identifier("b".to_string()));
+        expect_token(&mut lexer, Divide);
+        expect_token(&mut lexer, Identifier("c".to_string()));
+        expect_token(&mut lexer, Eof);
+    }
+
     #[test]
     fn parse_whitespace() {
         use tokens::TokenKind::*;
@@ -458,6 +1656,1425 @@ mod lexer {
         expect_token(&mut lexer, Eof);
 
         // Even with extra whitespace, the lexer should correctly reach the end
-        assert_eq!(lexer.position, input.len());
+        assert_eq!(lexer.offset(), input.len());
+    }
+
+    #[test]
+    fn parse_emit_whitespace() {
+        use tokens::TokenKind::*;
+
+        let input = "val  x\t=\n\t 5 \n";
+        let options = LexerOptions {
+            emit_whitespace: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::from_str(input).with_options(options);
+
+        let expected = [
+            Token::new(Val, 0, 3),
+            Token::new(Whitespace, 3, 2),
+            Token::new(Identifier("x".to_string()), 5, 1),
+            Token::new(Whitespace, 6, 1),
+            Token::new(Assign, 7, 1),
+            Token::new(Whitespace, 8, 3),
+            Token::new(IntLiteral(5), 11, 1),
+            Token::new(Whitespace, 12, 2),
+            Token::new(Eof, 14, 0),
+        ];
+        for token in expected {
+            assert_eq!(lexer.next_token(), Ok(token));
+        }
+
+        // A byte order mark is part of the whitespace run after it
+        let mut lexer = Lexer::from_str("\u{FEFF}  a").with_options(options);
+        assert_eq!(lexer.next_token(), Ok(Token::new(Whitespace, 0, 5)));
+        assert_eq!(
+            lexer.next_token(),
+            Ok(Token::new(Identifier("a".to_string()), 5, 1))
+        );
+        let mut lexer = Lexer::from_str("\u{FEFF}a").with_options(options);
+        assert_eq!(lexer.next_token(), Ok(Token::new(Whitespace, 0, 3)));
+        expect_token(&mut lexer, Identifier("a".to_string()));
+    }
+
+    #[test]
+    fn parse_emit_whitespace_around_comment() {
+        use tokens::TokenKind::*;
+
+        let input = "a // comment\n b";
+        let options = LexerOptions {
+            emit_whitespace: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::from_str(input).with_options(options);
+
+        expect_token(&mut lexer, Identifier("a".to_string()));
+        expect_token(&mut lexer, Whitespace);
+        // The comment itself is still skipped
+        assert_eq!(lexer.next_token(), Ok(Token::new(Whitespace, 12, 2)));
+        expect_token(&mut lexer, Identifier("b".to_string()));
+        expect_token(&mut lexer, Eof);
+    }
+
+    fn indentation_lexer(input: &str) -> Lexer<'_> {
+        let options = LexerOptions {
+            indentation: true,
+            ..LexerOptions::default()
+        };
+        Lexer::from_str(input).with_options(options)
+    }
+
+    #[test]
+    fn parse_indentation_nested_blocks() {
+        use tokens::TokenKind::*;
+
+        let input = "fn f():\n    if x:\n        y\n    z\nw\n";
+        let mut lexer = indentation_lexer(input);
+
+        expect_token(&mut lexer, Fn);
+        expect_token(&mut lexer, Identifier("f".to_string()));
+        expect_token(&mut lexer, LParen);
+        expect_token(&mut lexer, RParen);
+        expect_token(&mut lexer, Colon);
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Indent);
+        expect_token(&mut lexer, If);
+        expect_token(&mut lexer, Identifier("x".to_string()));
+        expect_token(&mut lexer, Colon);
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Indent);
+        expect_token(&mut lexer, Identifier("y".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Dedent);
+        expect_token(&mut lexer, Identifier("z".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Dedent);
+        expect_token(&mut lexer, Identifier("w".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_indentation_inside_brackets() {
+        use tokens::TokenKind::*;
+
+        let mut lexer = indentation_lexer("f(a,\n  b)\nc = [\n    1,\n]\n");
+        let kinds: Vec<_> = (0..15).map(|_| lexer.next_token().unwrap().kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Identifier("f".to_string()),
+                LParen,
+                Identifier("a".to_string()),
+                Comma,
+                Identifier("b".to_string()),
+                RParen,
+                Newline,
+                Identifier("c".to_string()),
+                Assign,
+                LBracket,
+                IntLiteral(1),
+                Comma,
+                RBracket,
+                Newline,
+                Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_indentation_multiple_dedents() {
+        use tokens::TokenKind::*;
+
+        let input = "a\n  b\n    c\nd";
+        let mut lexer = indentation_lexer(input);
+
+        expect_token(&mut lexer, Identifier("a".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Indent);
+        expect_token(&mut lexer, Identifier("b".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Indent);
+        expect_token(&mut lexer, Identifier("c".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Dedent);
+        expect_token(&mut lexer, Dedent);
+        expect_token(&mut lexer, Identifier("d".to_string()));
+        // The last line is terminated even without a trailing newline
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_indentation_closes_blocks_at_eof() {
+        use tokens::TokenKind::*;
+
+        let input = "a\n\tb\n\t\tc\n";
+        let mut lexer = indentation_lexer(input);
+
+        expect_token(&mut lexer, Identifier("a".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Indent);
+        expect_token(&mut lexer, Identifier("b".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Indent);
+        expect_token(&mut lexer, Identifier("c".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Dedent);
+        expect_token(&mut lexer, Dedent);
+        expect_token(&mut lexer, Eof);
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_indentation_skips_blank_lines() {
+        use tokens::TokenKind::*;
+
+        let input = "a\n\n    b\n        \n    // comment\r\n\n    c\n";
+        let mut lexer = indentation_lexer(input);
+
+        expect_token(&mut lexer, Identifier("a".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Indent);
+        expect_token(&mut lexer, Identifier("b".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Identifier("c".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Dedent);
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_indentation_spans() {
+        use tokens::TokenKind::*;
+
+        let input = "a\n  b";
+        let mut lexer = indentation_lexer(input);
+
+        lexer.next_token().unwrap();
+        assert_eq!(lexer.next_token(), Ok(Token::new(Newline, 1, 1)));
+        assert_eq!(lexer.next_token(), Ok(Token::new(Indent, 2, 2)));
+        assert_eq!(
+            lexer.next_token(),
+            Ok(Token::new(Identifier("b".to_string()), 4, 1))
+        );
+        assert_eq!(lexer.next_token(), Ok(Token::new(Newline, 5, 0)));
+        assert_eq!(lexer.next_token(), Ok(Token::new(Dedent, 5, 0)));
+    }
+
+    #[test]
+    fn parse_indentation_mixed_tabs_and_spaces() {
+        use tokens::TokenKind::*;
+
+        let input = "a\n\tb\n    c\n";
+        let mut lexer = indentation_lexer(input);
+
+        expect_token(&mut lexer, Identifier("a".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Indent);
+        expect_token(&mut lexer, Identifier("b".to_string()));
+        expect_token(&mut lexer, Newline);
+        assert_eq!(
+            lexer.next_token(),
+            Err(LexError::InconsistentIndent {
+                span: Span::new(5, 9),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_indentation_unknown_dedent_level() {
+        use tokens::TokenKind::*;
+
+        let input = "a\n    b\n  c\n";
+        let mut lexer = indentation_lexer(input);
+
+        expect_token(&mut lexer, Identifier("a".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Indent);
+        expect_token(&mut lexer, Identifier("b".to_string()));
+        expect_token(&mut lexer, Newline);
+        assert_eq!(
+            lexer.next_token(),
+            Err(LexError::InconsistentIndent {
+                span: Span::new(8, 10),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_line_continuation() {
+        let kinds =
+            |tokens: Vec<Token>| -> Vec<_> { tokens.into_iter().map(|token| token.kind).collect() };
+
+        let joined = tokenize("val x = 1 +\\\n    2 *\\\r\n3").unwrap();
+        assert_eq!(kinds(joined), kinds(tokenize("val x = 1 + 2 * 3").unwrap()));
+    }
+
+    #[test]
+    fn parse_line_continuation_in_indentation_mode() {
+        use tokens::TokenKind::*;
+
+        let mut lexer = indentation_lexer("a = 1 + \\\n    2\nb\n");
+
+        expect_token(&mut lexer, Identifier("a".to_string()));
+        expect_token(&mut lexer, Assign);
+        expect_token(&mut lexer, IntLiteral(1));
+        expect_token(&mut lexer, Plus);
+        // The continued line is part of the same logical line, so it is not indented
+        expect_token(&mut lexer, IntLiteral(2));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Identifier("b".to_string()));
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_emit_line_continuation() {
+        use tokens::TokenKind::*;
+
+        let options = LexerOptions {
+            emit_whitespace: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::from_str("a \\\n b").with_options(options);
+
+        expect_token(&mut lexer, Identifier("a".to_string()));
+        assert_eq!(lexer.next_token(), Ok(Token::new(Whitespace, 1, 4)));
+        expect_token(&mut lexer, Identifier("b".to_string()));
+    }
+
+    #[test]
+    fn parse_backslash_without_newline() {
+        use tokens::TokenKind::*;
+
+        let mut lexer = Lexer::from_str("a \\ b \\");
+
+        expect_token(&mut lexer, Identifier("a".to_string()));
+        assert_eq!(
+            lexer.next_token(),
+            Err(LexError::UnexpectedChar {
+                ch: '\\',
+                span: Span::new(2, 3),
+            })
+        );
+        expect_token(&mut lexer, Identifier("b".to_string()));
+        assert!(lexer.next_token().is_err());
+        expect_token(&mut lexer, Eof);
+    }
+
+    /// Lex the input in newline mode, returning the kinds of every token up to `Eof`.
+    fn newline_kinds(input: &str) -> Vec<TokenKind> {
+        let options = LexerOptions {
+            newlines: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::from_str(input).with_options(options);
+        let results = lex_results(&mut lexer, usize::MAX);
+        results
+            .into_iter()
+            .map(|result| result.unwrap().kind)
+            .collect()
+    }
+
+    #[test]
+    fn parse_newline_terminators() {
+        use tokens::TokenKind::*;
+
+        assert_eq!(
+            newline_kinds("val x = 1\r\nx\n"),
+            vec![
+                Val,
+                Identifier("x".to_string()),
+                Assign,
+                IntLiteral(1),
+                Newline,
+                Identifier("x".to_string()),
+                Newline,
+                Eof,
+            ]
+        );
+        let mut lexer = Lexer::from_str("a\nb").with_options(LexerOptions {
+            newlines: true,
+            ..LexerOptions::default()
+        });
+        lexer.next_token().unwrap();
+        assert_eq!(lexer.next_token(), Ok(Token::new(Newline, 1, 1)));
+    }
+
+    #[test]
+    fn parse_newlines_inside_brackets() {
+        use tokens::TokenKind::*;
+
+        assert_eq!(
+            newline_kinds("f(a,\n  b)[\n0\n]\nc"),
+            vec![
+                Identifier("f".to_string()),
+                LParen,
+                Identifier("a".to_string()),
+                Comma,
+                Identifier("b".to_string()),
+                RParen,
+                LBracket,
+                IntLiteral(0),
+                RBracket,
+                Newline,
+                Identifier("c".to_string()),
+                Eof,
+            ]
+        );
+        assert_eq!(
+            newline_kinds("x = {\na,\n(b\n)}\ny"),
+            vec![
+                Identifier("x".to_string()),
+                Assign,
+                LBrace,
+                Identifier("a".to_string()),
+                Comma,
+                LParen,
+                Identifier("b".to_string()),
+                RParen,
+                RBrace,
+                Newline,
+                Identifier("y".to_string()),
+                Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_newlines_skip_blank_lines() {
+        use tokens::TokenKind::*;
+
+        assert_eq!(
+            newline_kinds("\n\na // one\n\n  // two\n\nb\n\n"),
+            vec![
+                Identifier("a".to_string()),
+                Newline,
+                Identifier("b".to_string()),
+                Newline,
+                Eof,
+            ]
+        );
+        // A backslash still continues the line
+        assert_eq!(
+            newline_kinds("a \\\n+ b\n"),
+            vec![
+                Identifier("a".to_string()),
+                Plus,
+                Identifier("b".to_string()),
+                Newline,
+                Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_newline_after_error() {
+        use tokens::TokenKind::*;
+
+        let options = LexerOptions {
+            newlines: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::from_str("\"\\q\"\n$\nb").with_options(options);
+
+        assert!(lexer.next_token().is_err());
+        expect_token(&mut lexer, StringLiteral("".to_string()));
+        expect_token(&mut lexer, Newline);
+        assert!(lexer.next_token().is_err());
+        expect_token(&mut lexer, Newline);
+        expect_token(&mut lexer, Identifier("b".to_string()));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn advance_clamps_to_input_length() {
+        // Three characters, but six bytes
+        let input = "\u{e9}\u{e9}\u{e9}";
+        let mut lexer = Lexer::from_str(input);
+
+        lexer.advance(2);
+        assert_eq!(lexer.offset(), 4);
+        assert_eq!(lexer.peek(0), Some('\u{e9}'));
+        lexer.advance(2);
+        assert_eq!(lexer.offset(), 6);
+        assert_eq!(lexer.peek(0), None);
+
+        lexer.advance(usize::MAX);
+        assert_eq!(lexer.offset(), 6);
+    }
+
+    #[test]
+    fn peek_large_offset_does_not_overflow() {
+        let mut lexer = Lexer::from_str("ab");
+        lexer.advance(1);
+
+        assert_eq!(lexer.peek(usize::MAX), None);
+        assert_eq!(lexer.peek(1), None);
+        assert_eq!(lexer.peek(0), Some('b'));
+    }
+
+    #[test]
+    fn parse_with_file() {
+        let file = FileId(7);
+        let mut lexer = Lexer::from_str("val x = $").with_file(file);
+
+        for _ in 0..3 {
+            let span = lexer.next_token().unwrap().pos;
+            assert_eq!(lexer.file_span(span), FileSpan { file, span });
+        }
+        let error = lexer.next_token().unwrap_err();
+        assert_eq!(lexer.file_span(error.span()).file, file);
+        assert_eq!(
+            lexer.file_span(Span::empty_at(9)).to_string(),
+            "file 7 [9..9]"
+        );
+    }
+
+    #[test]
+    fn parse_default_file() {
+        let lexer = Lexer::from_str("val x = 5");
+        assert_eq!(lexer.file_span(Span::new(0, 3)).file, FileId::default());
+    }
+
+    #[test]
+    fn parse_rest_shrinks() {
+        let input = "val x = 5 // done";
+        let mut lexer = Lexer::from_str(input);
+        assert_eq!(lexer.rest(), input);
+        assert_eq!(lexer.offset(), 0);
+
+        lexer.next_token().unwrap();
+        assert_eq!(lexer.rest(), " x = 5 // done");
+        assert_eq!(lexer.offset(), 3);
+
+        lexer.next_token().unwrap();
+        lexer.next_token().unwrap();
+        assert_eq!(lexer.rest(), " 5 // done");
+
+        lexer.next_token().unwrap();
+        expect_token(&mut lexer, TokenKind::Eof);
+        assert_eq!(lexer.rest(), "");
+        assert_eq!(lexer.offset(), input.len());
+    }
+
+    #[test]
+    fn parse_rest_after_non_ascii() {
+        let mut lexer = Lexer::from_str("\u{e9}\u{e9} x y");
+
+        assert!(lexer.next_token().is_err());
+        assert_eq!(lexer.rest(), "\u{e9} x y");
+    }
+
+    /// Lex up to `count` results, stopping early at `Eof`.
+    fn lex_results(lexer: &mut Lexer, count: usize) -> Vec<Result<Token, LexError>> {
+        let mut results = Vec::new();
+        for _ in 0..count {
+            let result = lexer.next_token();
+            let done = matches!(&result, Ok(token) if token.is_eof());
+            results.push(result);
+            if done {
+                break;
+            }
+        }
+        results
+    }
+
+    #[test]
+    fn parse_checkpoint_and_restore() {
+        let mut lexer = Lexer::from_str("val x = f(a, b) + 1");
+        lex_results(&mut lexer, 3);
+
+        let checkpoint = lexer.checkpoint();
+        let ahead = lex_results(&mut lexer, 4);
+        lexer.restore(checkpoint.clone());
+        assert_eq!(lex_results(&mut lexer, 4), ahead);
+
+        // A checkpoint can be restored more than once
+        lexer.restore(checkpoint);
+        assert_eq!(lexer.offset(), 7);
+        let rest = lex_results(&mut lexer, usize::MAX);
+        assert_eq!(&rest[..4], &ahead[..]);
+        assert!(rest.last().unwrap().as_ref().unwrap().is_eof());
+    }
+
+    #[test]
+    fn parse_restore_replays_pending() {
+        let mut lexer = indentation_lexer("a:\n    b\n    \"x\\q\"\nc\n");
+        lex_results(&mut lexer, 6);
+
+        // Restoring between an error and the literal it was reported for replays both in order
+        assert!(lexer.next_token().is_err());
+        let checkpoint = lexer.checkpoint();
+        let ahead = lex_results(&mut lexer, usize::MAX);
+        lexer.restore(checkpoint);
+        assert_eq!(lex_results(&mut lexer, usize::MAX), ahead);
+    }
+
+    #[test]
+    fn parse_restore_forgets_interned_names() {
+        let options = LexerOptions {
+            intern_identifiers: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::from_str("a b c").with_options(options);
+        lexer.next_token().unwrap();
+
+        let checkpoint = lexer.checkpoint();
+        lex_results(&mut lexer, usize::MAX);
+        assert_eq!(lexer.interner().len(), 3);
+        lexer.restore(checkpoint);
+        assert_eq!(lexer.interner().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "checkpoint was created by a different lexer")]
+    fn parse_restore_from_other_lexer() {
+        let input = "val x = 5";
+        let mut first = Lexer::from_str(input);
+        let mut second = Lexer::from_str(input);
+        first.next_token().unwrap();
+
+        second.restore(first.checkpoint());
+    }
+
+    #[test]
+    fn parse_reset() {
+        let mut lexer = indentation_lexer("a:\n    b\n");
+        let all = lex_results(&mut lexer, usize::MAX);
+
+        lexer.reset();
+        assert_eq!(lexer.offset(), 0);
+        assert_eq!(lex_results(&mut lexer, usize::MAX), all);
+    }
+
+    #[test]
+    fn parse_relex() {
+        let options = LexerOptions {
+            intern_identifiers: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::from_str("count + 1 + ").with_options(options);
+        let first = lex_results(&mut lexer, 3);
+
+        // Leftover input is dropped, and the new input starts at offset zero
+        lexer.relex("total - count".to_string());
+        let second = lex_results(&mut lexer, usize::MAX);
+
+        let mut fresh = Lexer::from_str("count + 1").with_options(options);
+        assert_eq!(first, lex_results(&mut fresh, 3));
+        let [total, minus, count, eof] = &second[..] else {
+            panic!("expected four tokens, got {:?}", second);
+        };
+        assert_eq!(total.as_ref().unwrap().pos, Span::new(0, 5));
+        assert_eq!(minus.as_ref().unwrap().kind, TokenKind::Minus);
+        // The interner is shared, so the same name gets the same symbol in both inputs
+        assert_eq!(
+            count.as_ref().unwrap().kind,
+            first[0].as_ref().unwrap().kind
+        );
+        assert!(eof.as_ref().unwrap().is_eof());
+        assert_eq!(lexer.interner().len(), 2);
+    }
+
+    #[test]
+    fn parse_relex_keeps_keywords() {
+        let mut lexer = Lexer::from_str("let").with_keywords(&["let"]);
+        lexer.next_token().unwrap();
+        lexer.relex("x let".to_string());
+
+        lexer.next_token().unwrap();
+        expect_token(&mut lexer, TokenKind::CustomKeyword("let".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "checkpoint was created by a different lexer")]
+    fn parse_relex_invalidates_checkpoints() {
+        let mut lexer = Lexer::from_str("a b");
+        lexer.next_token().unwrap();
+        let checkpoint = lexer.checkpoint();
+
+        lexer.relex("c".to_string());
+        lexer.restore(checkpoint);
+    }
+
+    #[test]
+    fn parse_keyword_and_identifier_spans() {
+        let input = "\"\u{e9}\" elif  counter_2 _";
+        let tokens = tokenize(input).unwrap();
+
+        let text = |span: Span| span.slice(input).unwrap();
+        assert_eq!(tokens[1].pos, Span::new(5, 9));
+        assert_eq!(text(tokens[1].pos), "elif");
+        assert_eq!(tokens[2].pos, Span::new(11, 20));
+        assert_eq!(text(tokens[2].pos), "counter_2");
+        assert_eq!(text(tokens[3].pos), "_");
+    }
+
+    #[test]
+    fn parse_byte_offsets_after_non_ascii() {
+        use tokens::TokenKind::*;
+
+        // One, two, three and four byte characters before the tokens
+        let input =
+            "\"a\u{e9}\u{20ac}\u{1F600}\" val x = '\u{e9}' // \u{20ac}\n/// \u{e9}t\u{e9}\ny";
+        let tokens = tokenize(input).unwrap();
+
+        let slices: Vec<_> = tokens
+            .iter()
+            .map(|token| token.pos.slice(input).unwrap())
+            .collect();
+        assert_eq!(
+            slices,
+            vec![
+                "\"a\u{e9}\u{20ac}\u{1F600}\"",
+                "val",
+                "x",
+                "=",
+                "'\u{e9}'",
+                "/// \u{e9}t\u{e9}",
+                "y",
+                ""
+            ]
+        );
+        assert_eq!(tokens[1].pos, Span::new(13, 16));
+        assert_eq!(tokens[5].kind, DocComment(" \u{e9}t\u{e9}".to_string()));
+        assert_eq!(tokens.last().unwrap().pos, Span::empty_at(input.len()));
+    }
+
+    #[test]
+    fn parse_error_byte_offsets() {
+        let input = "\u{e9} \u{20ac}";
+        let (_, errors) = tokenize_all(input);
+
+        let slices: Vec<_> = errors
+            .iter()
+            .map(|error| error.span().slice(input).unwrap())
+            .collect();
+        assert_eq!(slices, vec!["\u{e9}", "\u{20ac}"]);
+    }
+
+    #[test]
+    fn parse_borrowed_input() {
+        use tokens::TokenKind::*;
+
+        let input = String::from("val x = 5");
+        let mut lexer = Lexer::from_str(&input);
+
+        expect_token(&mut lexer, Val);
+        expect_token(&mut lexer, Identifier("x".to_string()));
+        expect_token(&mut lexer, Assign);
+        expect_token(&mut lexer, IntLiteral(5));
+        expect_token(&mut lexer, Eof);
+
+        // The lexer should not have taken a copy of the input
+        assert!(matches!(lexer.input, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn parse_unexpected_character() {
+        let mut lexer = Lexer::new("val x = $".to_string());
+        for _ in 0..3 {
+            lexer.next_token().unwrap();
+        }
+
+        assert_eq!(
+            lexer.next_token(),
+            Err(LexError::UnexpectedChar {
+                ch: '$',
+                span: Span::new(8, 9),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_integer_overflow() {
+        use tokens::TokenKind::*;
+
+        let input = "99999999999999999999999 1";
+        let mut lexer = Lexer::new(input.to_string());
+
+        assert_eq!(
+            lexer.next_token(),
+            Err(LexError::IntOverflow {
+                span: Span::new(0, 23),
+            })
+        );
+        // Lexing resumes after the oversized literal
+        expect_token(&mut lexer, IntLiteral(1));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_big_integers() {
+        use tokens::TokenKind::*;
+
+        let input = "99999999999999999999999 1";
+        let options = LexerOptions {
+            big_integers: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::from_str(input).with_options(options);
+
+        assert_eq!(
+            lexer.next_token(),
+            Ok(Token::new(
+                BigIntLiteral("99999999999999999999999".to_string()),
+                0,
+                23
+            ))
+        );
+        // Literals that fit are unchanged
+        expect_token(&mut lexer, IntLiteral(1));
+        expect_token(&mut lexer, Eof);
+
+        // The same literal is still an error by default
+        assert!(matches!(
+            Lexer::from_str(input).next_token(),
+            Err(LexError::IntOverflow { .. })
+        ));
+    }
+
+    #[test]
+    fn parse_control_characters() {
+        use tokens::TokenKind::*;
+
+        let (tokens, errors) = tokenize_all("a\0b\x0Cc\x0B\x1B d");
+
+        assert_eq!(
+            errors,
+            vec![
+                LexError::UnexpectedChar {
+                    ch: '\0',
+                    span: Span::new(1, 2),
+                },
+                LexError::UnexpectedChar {
+                    ch: '\x0C',
+                    span: Span::new(3, 4),
+                },
+                LexError::UnexpectedChar {
+                    ch: '\x0B',
+                    span: Span::new(5, 6),
+                },
+                LexError::UnexpectedChar {
+                    ch: '\x1B',
+                    span: Span::new(6, 7),
+                },
+            ]
+        );
+        let kinds: Vec<_> = tokens.into_iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Identifier("a".to_string()),
+                Identifier("b".to_string()),
+                Identifier("c".to_string()),
+                Identifier("d".to_string()),
+                Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_byte_order_mark() {
+        use tokens::TokenKind::*;
+
+        let input = "\u{FEFF}val x = 5";
+        let mut lexer = Lexer::from_str(input);
+
+        // Spans stay byte offsets into the input, so they start after the mark
+        assert_eq!(lexer.next_token(), Ok(Token::new(Val, 3, 3)));
+        expect_token(&mut lexer, Identifier("x".to_string()));
+        expect_token(&mut lexer, Assign);
+        expect_token(&mut lexer, IntLiteral(5));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_byte_order_mark_after_start() {
+        for input in ["a \u{FEFF}", " \u{FEFF}a", "\u{FEFF}\u{FEFF}"] {
+            let (_, errors) = tokenize_all(input);
+            assert!(
+                matches!(
+                    errors[..],
+                    [LexError::UnexpectedChar {
+                        ch: BYTE_ORDER_MARK,
+                        ..
+                    }]
+                ),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn parse_max_token_len() {
+        use tokens::TokenKind::*;
+
+        let options = LexerOptions {
+            max_token_len: Some(8),
+            ..LexerOptions::default()
+        };
+        let input = "abcdefghijklmnopqrst + 12345678 123456789 exactly8";
+        let mut lexer = Lexer::from_str(input).with_options(options);
+
+        assert_eq!(
+            lexer.next_token(),
+            Err(LexError::TokenTooLong {
+                span: Span::new(0, 20),
+            })
+        );
+        expect_token(&mut lexer, Plus);
+        expect_token(&mut lexer, IntLiteral(12345678));
+        assert_eq!(
+            lexer.next_token(),
+            Err(LexError::TokenTooLong {
+                span: Span::new(32, 41),
+            })
+        );
+        expect_token(&mut lexer, Identifier("exactly8".to_string()));
+        expect_token(&mut lexer, Eof);
+
+        // There is no limit by default
+        assert!(tokenize(&"a".repeat(1_000)).is_ok());
+    }
+
+    #[test]
+    fn parse_non_ascii_before_identifier() {
+        use tokens::TokenKind::*;
+
+        let input = "// caf\u{e9}\nabc \u{a0}def";
+        let mut lexer = Lexer::new(input.to_string());
+
+        expect_token(&mut lexer, Identifier("abc".to_string()));
+        expect_token(&mut lexer, Identifier("def".to_string()));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_long_whitespace_run() {
+        let input = " ".repeat(10_000);
+        let mut lexer = Lexer::new(input);
+
+        expect_token(&mut lexer, TokenKind::Eof);
+    }
+
+    /// Small xorshift generator so randomised tests are deterministic without extra dependencies.
+    fn random_generator() -> impl FnMut() -> u64 {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        }
+    }
+
+    /// Checks that the lexer reaches `Eof` within `limit` calls, errors included.
+    fn reaches_eof(lexer: &mut Lexer, limit: usize) -> bool {
+        (0..limit).any(|_| {
+            matches!(
+                lexer.next_token(),
+                Ok(Token {
+                    kind: TokenKind::Eof,
+                    ..
+                })
+            )
+        })
+    }
+
+    #[test]
+    fn parse_random_input_never_panics() {
+        let mut next = random_generator();
+
+        for _ in 0..2_000 {
+            let len = (next() % 48) as usize;
+            let bytes: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+            let input = String::from_utf8_lossy(&bytes).into_owned();
+            // An unterminated string yields both an error and a token, so allow two calls per character
+            let limit = 2 * (input.chars().count() + 1);
+            let mut lexer = Lexer::new(input);
+
+            assert!(reaches_eof(&mut lexer, limit), "lexer did not terminate");
+        }
+    }
+
+    #[test]
+    fn parse_emit_comments() {
+        use tokens::TokenKind::*;
+
+        let options = LexerOptions {
+            emit_comments: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::from_str("a // note\r\n/// doc\n//// rule").with_options(options);
+
+        expect_token(&mut lexer, Identifier("a".to_string()));
+        assert_eq!(
+            lexer.next_token(),
+            Ok(Token::new(Comment(" note".to_string()), 2, 7))
+        );
+        expect_token(&mut lexer, DocComment(" doc".to_string()));
+        expect_token(&mut lexer, Comment("// rule".to_string()));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_trivia_round_trip() {
+        let input =
+            "\u{FEFF}/// Adds.\r\nfn add(a: int) {\n\t// sum\n    a +\\\n 'b' \"c\\n\"  \n}";
+        let tokens = tokenize_with_trivia(input).unwrap();
+        assert_eq!(stream::reconstruct(&tokens, input).as_deref(), Some(input));
+    }
+
+    #[test]
+    fn parse_random_trivia_round_trip() {
+        let mut next = random_generator();
+        let fragments = [
+            " ",
+            "\t",
+            "\n",
+            "\r\n",
+            "\\\n",
+            "// c",
+            "/// d",
+            "//// e",
+            "x",
+            "_y2",
+            "val",
+            "42",
+            "\"s\\t\"",
+            "'\u{e9}'",
+            "\"\u{e9}\"",
+            "\u{3000}",
+            "+",
+            "-",
+            "/",
+            "==",
+            "=",
+            ">>",
+            "<=",
+            "%=",
+            "(",
+            "]",
+            "&&",
+            "@",
+            "?",
+        ];
+
+        for _ in 0..2_000 {
+            let len = (next() % 24) as usize;
+            let mut input: String = (0..len)
+                .map(|_| fragments[next() as usize % fragments.len()])
+                .collect();
+            if next().is_multiple_of(8) {
+                input.insert(0, BYTE_ORDER_MARK);
+            }
+
+            let tokens = match tokenize_with_trivia(&input) {
+                Ok(tokens) => tokens,
+                Err(error) => panic!("failed to lex {:?}: {}", input, error),
+            };
+            assert_eq!(
+                stream::reconstruct(&tokens, &input),
+                Some(input.clone()),
+                "{:?}",
+                input
+            );
+            // Each run of whitespace is one token, byte order mark included
+            assert!(
+                !tokens
+                    .windows(2)
+                    .any(|pair| pair[0].kind == TokenKind::Whitespace
+                        && pair[1].kind == TokenKind::Whitespace),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn parse_random_chars_trivia_round_trip() {
+        let mut next = random_generator();
+        let wide = [
+            '\u{e9}',
+            '\u{3000}',
+            '\u{a0}',
+            '\u{200b}',
+            BYTE_ORDER_MARK,
+            '\u{1f600}',
+        ];
+
+        // Inputs that fail to lex are out of scope, as there are no tokens to rebuild them from
+        let mut lexed = 0;
+        for _ in 0..20_000 {
+            let len = (next() % 12) as usize;
+            let input: String = (0..len)
+                .map(|_| match next() % 8 {
+                    0 => wide[next() as usize % wide.len()],
+                    // Any ASCII character, control characters included
+                    _ => char::from((next() % 128) as u8),
+                })
+                .collect();
+
+            if let Ok(tokens) = tokenize_with_trivia(&input) {
+                lexed += 1;
+                assert_eq!(
+                    stream::reconstruct(&tokens, &input),
+                    Some(input.clone()),
+                    "{:?}",
+                    input
+                );
+            }
+        }
+        // Enough inputs must lex for the round trip to be exercised
+        assert!(lexed > 1_000, "only {} inputs lexed", lexed);
+    }
+
+    #[test]
+    fn parse_random_indentation_terminates() {
+        let mut next = random_generator();
+        let alphabet = [' ', '\t', '\n', '\r', 'a', '1', '/', '(', '&', '\u{e9}'];
+
+        for _ in 0..2_000 {
+            let len = (next() % 48) as usize;
+            let input: String = (0..len)
+                .map(|_| alphabet[next() as usize % alphabet.len()])
+                .collect();
+            // Layout tokens consume no input, but there are at most a few per character
+            let limit = 3 * (input.chars().count() + 1);
+            let mut lexer = indentation_lexer(&input);
+
+            assert!(reaches_eof(&mut lexer, limit), "lexer did not terminate");
+        }
+    }
+
+    #[test]
+    fn parse_string_literals() {
+        use tokens::TokenKind::*;
+
+        let input = r#"val s = "hello, world" "a\"b\\c\n""#;
+        let mut lexer = Lexer::from_str(input);
+
+        expect_token(&mut lexer, Val);
+        expect_token(&mut lexer, Identifier("s".to_string()));
+        expect_token(&mut lexer, Assign);
+        assert_eq!(
+            lexer.next_token(),
+            Ok(Token::new(StringLiteral("hello, world".to_string()), 8, 14))
+        );
+        expect_token(&mut lexer, StringLiteral("a\"b\\c\n".to_string()));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_invalid_escape() {
+        use tokens::TokenKind::*;
+
+        let mut lexer = Lexer::from_str(r#""a\qb" x"#);
+
+        assert_eq!(
+            lexer.next_token(),
+            Err(LexError::InvalidEscape {
+                span: Span::new(2, 4),
+            })
+        );
+        // The rest of the string is still read
+        expect_token(&mut lexer, StringLiteral("ab".to_string()));
+        expect_token(&mut lexer, Identifier("x".to_string()));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_unterminated_string() {
+        use tokens::TokenKind::*;
+
+        let input = "val s = \"abc";
+        let mut lexer = Lexer::from_str(input);
+        for _ in 0..3 {
+            lexer.next_token().unwrap();
+        }
+
+        assert_eq!(
+            lexer.next_token(),
+            Err(LexError::UnterminatedString {
+                span: Span::new(8, 12),
+            })
+        );
+        assert_eq!(
+            lexer.next_token(),
+            Ok(Token::new(StringLiteral("abc".to_string()), 8, 4))
+        );
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_unterminated_string_recovery() {
+        use tokens::TokenKind::*;
+
+        let (tokens, errors) = tokenize_all("x = \"abc\\");
+
+        assert_eq!(
+            errors,
+            vec![
+                LexError::InvalidEscape {
+                    span: Span::new(8, 9),
+                },
+                LexError::UnterminatedString {
+                    span: Span::new(4, 9),
+                },
+            ]
+        );
+        let kinds: Vec<_> = tokens.into_iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Identifier("x".to_string()),
+                Assign,
+                StringLiteral("abc".to_string()),
+                Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_unicode_and_hex_escapes() {
+        use tokens::TokenKind::*;
+
+        let mut lexer = Lexer::from_str(r#""\u{1F600} \x41\u{e9}" '\u{1F600}' '\x41'"#);
+
+        expect_token(&mut lexer, StringLiteral("\u{1F600} A\u{e9}".to_string()));
+        expect_token(&mut lexer, CharLiteral('\u{1F600}'));
+        expect_token(&mut lexer, CharLiteral('A'));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_hex_escape_above_ascii() {
+        use tokens::TokenKind::*;
+
+        let mut lexer = Lexer::from_str(r#""\xFF""#);
+        expect_token(&mut lexer, StringLiteral("\u{ff}".to_string()));
+    }
+
+    #[test]
+    fn parse_malformed_escapes() {
+        for (input, span, value) in [
+            // Empty braces
+            (r#""\u{}""#, 1..5, ""),
+            // Missing braces
+            (r#""\uGGGG""#, 1..3, "GGGG"),
+            // Above the last code point
+            (r#""\u{110000}""#, 1..11, ""),
+            // Surrogate
+            (r#""\u{D800}""#, 1..9, ""),
+            // Too many digits, even with leading zeros
+            (r#""\u{0000041}""#, 1..12, ""),
+            // Unclosed braces
+            (r#""\u{41""#, 1..6, ""),
+            // Too few hex digits
+            (r#""\x4""#, 1..4, ""),
+            (r#""\xG1""#, 1..3, "G1"),
+        ] {
+            let mut lexer = Lexer::from_str(input);
+            assert_eq!(
+                lexer.next_token(),
+                Err(LexError::InvalidEscape { span: span.into() }),
+                "{}",
+                input
+            );
+            expect_token(
+                &mut lexer,
+                tokens::TokenKind::StringLiteral(value.to_string()),
+            );
+            expect_token(&mut lexer, tokens::TokenKind::Eof);
+        }
+    }
+
+    #[test]
+    fn parse_char_literals() {
+        use tokens::TokenKind::*;
+
+        let mut lexer = Lexer::from_str(r"'a' '\n' '\'' 'é'");
+
+        assert_eq!(lexer.next_token(), Ok(Token::new(CharLiteral('a'), 0, 3)));
+        expect_token(&mut lexer, CharLiteral('\n'));
+        expect_token(&mut lexer, CharLiteral('\''));
+        expect_token(&mut lexer, CharLiteral('é'));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_invalid_char_literals() {
+        use tokens::TokenKind::*;
+
+        let (tokens, errors) = tokenize_all("'' 'ab' 'c\nd");
+
+        assert_eq!(
+            errors,
+            vec![
+                LexError::InvalidCharLiteral {
+                    span: Span::new(0, 2),
+                },
+                LexError::InvalidCharLiteral {
+                    span: Span::new(3, 7),
+                },
+                LexError::InvalidCharLiteral {
+                    span: Span::new(8, 10),
+                },
+            ]
+        );
+        let kinds: Vec<_> = tokens.into_iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                CharLiteral(char::REPLACEMENT_CHARACTER),
+                CharLiteral('a'),
+                CharLiteral('c'),
+                Identifier("d".to_string()),
+                Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_tokenize_all() {
+        use tokens::TokenKind::*;
+
+        let (tokens, errors) = tokenize_all("a $ b");
+
+        assert_eq!(
+            errors,
+            vec![LexError::UnexpectedChar {
+                ch: '$',
+                span: Span::new(2, 3),
+            }]
+        );
+        let kinds: Vec<_> = tokens.into_iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Identifier("a".to_string()),
+                Identifier("b".to_string()),
+                Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_lex_one() {
+        use tokens::TokenKind::*;
+
+        assert_eq!(lex_one("+"), Ok(Token::new(Plus, 0, 1)));
+        assert_eq!(
+            lex_one("  >= // compare\n"),
+            Ok(Token::new(GreaterThanOrEqual, 2, 2))
+        );
+        assert_eq!(lex_one(" "), Ok(Token::new(Eof, 1, 0)));
+    }
+
+    #[test]
+    fn parse_lex_one_trailing_input() {
+        assert_eq!(
+            lex_one("+ 1 2"),
+            Err(LexError::TrailingInput {
+                span: Span::new(2, 5),
+            })
+        );
+        assert_eq!(
+            lex_one("a $"),
+            Err(LexError::TrailingInput {
+                span: Span::new(2, 3),
+            })
+        );
+        // An error in the token itself is reported as is
+        assert!(matches!(lex_one("$"), Err(LexError::UnexpectedChar { .. })));
+    }
+
+    #[test]
+    fn parse_extra_keywords() {
+        use tokens::TokenKind::*;
+
+        let input = "let mut x: type = val letter";
+        let mut lexer = Lexer::new_with_keywords(input.to_string(), &["let", "mut", "type", "val"]);
+
+        assert_eq!(
+            lexer.next_token(),
+            Ok(Token::new(CustomKeyword("let".to_string()), 0, 3))
+        );
+        expect_token(&mut lexer, CustomKeyword("mut".to_string()));
+        expect_token(&mut lexer, Identifier("x".to_string()));
+        expect_token(&mut lexer, Colon);
+        expect_token(&mut lexer, CustomKeyword("type".to_string()));
+        expect_token(&mut lexer, Assign);
+        // Built-in keywords keep their own tokens
+        expect_token(&mut lexer, Val);
+        expect_token(&mut lexer, Identifier("letter".to_string()));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_extra_keywords_not_registered() {
+        use tokens::TokenKind::*;
+
+        let mut lexer = Lexer::from_str("let x").with_keywords(&["mut"]);
+
+        expect_token(&mut lexer, Identifier("let".to_string()));
+        expect_token(&mut lexer, Identifier("x".to_string()));
+        expect_token(&mut lexer, Eof);
+    }
+
+    #[test]
+    fn parse_interned_identifiers() {
+        use tokens::TokenKind::*;
+
+        let (tokens, interner) = tokenize_interned("val x = y + x").unwrap();
+
+        let symbols: Vec<_> = tokens
+            .iter()
+            .filter_map(|token| match token.kind {
+                InternedIdentifier(symbol) => Some(symbol),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(symbols.len(), 3);
+        assert_eq!(symbols[0], symbols[2]);
+        assert_ne!(symbols[0], symbols[1]);
+        assert_eq!(interner.resolve(symbols[0]), Some("x"));
+        assert_eq!(interner.resolve(symbols[1]), Some("y"));
+        assert_eq!(interner.len(), 2);
+
+        // Keywords are never interned
+        assert_eq!(tokens[0].kind, Val);
+    }
+
+    #[test]
+    fn parse_tokenize_large_input() {
+        use tokens::TokenKind::*;
+
+        // Dense input produces more tokens than the capacity estimate
+        let input = "a+b*c ".repeat(1_000);
+        let tokens = tokenize(&input).unwrap();
+
+        assert_eq!(tokens.len(), 5 * 1_000 + 1);
+        assert_eq!(tokens.last().unwrap(), &Token::new(Eof, input.len(), 0));
+        assert_eq!(tokens[4995].kind, Identifier("a".to_string()));
+        assert_eq!(tokens[4999].kind, Identifier("c".to_string()));
+    }
+
+    #[test]
+    fn parse_tokenize() {
+        use tokens::TokenKind::*;
+
+        let tokens = tokenize("val x = 5").unwrap();
+        let kinds: Vec<_> = tokens.into_iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![Val, Identifier("x".to_string()), Assign, IntLiteral(5), Eof]
+        );
+
+        assert!(tokenize("a & b").is_err());
     }
 }
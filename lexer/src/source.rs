@@ -0,0 +1,278 @@
+use std::fmt::Display;
+
+use crate::span::Span;
+
+/// A 1-based line and column position in a source string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Display for LineCol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+/// Default number of columns between tab stops.
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// Line index over a source string, mapping byte offsets to lines and columns.
+pub struct SourceMap<'a> {
+    source: &'a str,
+    /// Byte offset of the first character of each line.
+    line_starts: Vec<usize>,
+    /// Number of columns between tab stops.
+    tab_width: usize,
+}
+
+impl<'a> SourceMap<'a> {
+    /// Build the line index for the given source.
+    pub fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceMap {
+            source,
+            line_starts,
+            tab_width: DEFAULT_TAB_WIDTH,
+        }
+    }
+
+    /// Set the number of columns between tab stops, used when computing visual columns.
+    /// Defaults to [`DEFAULT_TAB_WIDTH`]; a width of zero is treated as one.
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
+
+    /// Get the number of columns between tab stops.
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    /// Get the source this map was built from.
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// Get the number of lines in the source.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Get the 0-based index of the line containing `offset`.
+    /// Offsets past the end of the source map to the last line.
+    pub fn line_index(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        }
+    }
+
+    /// Get the text of the line at the 0-based `line` index, without its line terminator.
+    /// Returns `None` if the line does not exist.
+    pub fn line_text(&self, line: usize) -> Option<&'a str> {
+        let start = *self.line_starts.get(line)?;
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .map_or(self.source.len(), |next| next - 1);
+        let text = &self.source[start..end];
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    /// Get the span of the whole line containing `offset`, without its line terminator.
+    /// Offsets past the end of the source fall on the last line.
+    pub fn line_span(&self, offset: usize) -> Span {
+        let line = self.line_index(self.clamp(offset));
+        let start = self.line_starts[line];
+        let len = self.line_text(line).map_or(0, str::len);
+        Span::new(start, start + len)
+    }
+
+    /// Get the lines overlapping `span`, along with up to `context` lines before and after them,
+    /// stopping at the start and end of the source.
+    /// Each line is paired with its 1-based line number, and excludes its line terminator.
+    pub fn snippet(&self, span: Span, context: usize) -> Vec<(usize, &'a str)> {
+        let first = self.line_index(self.clamp(span.start));
+        // The end is exclusive, so a span ending at the start of a line does not include it
+        let last_offset = if span.is_empty() {
+            span.end
+        } else {
+            span.end - 1
+        };
+        let last = self.line_index(self.clamp(last_offset)).max(first);
+
+        let from = first.saturating_sub(context);
+        let to = last.saturating_add(context).min(self.line_count() - 1);
+        (from..=to)
+            .filter_map(|line| Some((line + 1, self.line_text(line)?)))
+            .collect()
+    }
+
+    /// Get the 1-based line and column of `offset`.
+    /// The column is visual: it counts characters, not bytes, from the start of the line,
+    /// with a tab advancing to the next tab stop.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let offset = self.clamp(offset);
+        let line = self.line_index(offset);
+        let start = self.line_starts[line];
+        LineCol {
+            line: line + 1,
+            col: self.visual_width(&self.source[start..offset]) + 1,
+        }
+    }
+
+    /// Get the number of columns `text` occupies when it starts at a tab stop.
+    pub fn visual_width(&self, text: &str) -> usize {
+        text.chars().fold(0, |col, ch| self.next_col(col, ch))
+    }
+
+    /// Replace tabs in `text` with spaces up to the next tab stop,
+    /// so it lines up with the columns reported by [`SourceMap::line_col`].
+    pub fn expand_tabs(&self, text: &str) -> String {
+        let mut expanded = String::with_capacity(text.len());
+        for ch in text.chars() {
+            if ch == '\t' {
+                let col = expanded.chars().count();
+                let width = self.next_col(col, ch) - col;
+                expanded.extend(std::iter::repeat_n(' ', width));
+            } else {
+                expanded.push(ch);
+            }
+        }
+        expanded
+    }
+
+    /// Get the 0-based column following `ch` when it is at 0-based column `col`.
+    fn next_col(&self, col: usize, ch: char) -> usize {
+        if ch == '\t' {
+            (col / self.tab_width + 1) * self.tab_width
+        } else {
+            col + 1
+        }
+    }
+
+    /// Clamp `offset` to the source length and back onto a character boundary.
+    fn clamp(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_col() {
+        let map = SourceMap::new("val x = 5\nval y = 10\n");
+        assert_eq!(map.line_col(0), LineCol { line: 1, col: 1 });
+        assert_eq!(map.line_col(4), LineCol { line: 1, col: 5 });
+        assert_eq!(map.line_col(10), LineCol { line: 2, col: 1 });
+        assert_eq!(map.line_col(14), LineCol { line: 2, col: 5 });
+    }
+
+    #[test]
+    fn test_line_text() {
+        let map = SourceMap::new("first\r\nsecond\nthird");
+        assert_eq!(map.line_count(), 3);
+        assert_eq!(map.line_text(0), Some("first"));
+        assert_eq!(map.line_text(1), Some("second"));
+        assert_eq!(map.line_text(2), Some("third"));
+        assert_eq!(map.line_text(3), None);
+    }
+
+    #[test]
+    fn test_line_span() {
+        let source = "first\r\nsecond\nlast";
+        let map = SourceMap::new(source);
+
+        assert_eq!(map.line_span(0), Span::new(0, 5));
+        assert_eq!(map.line_span(3), Span::new(0, 5));
+        // The carriage return belongs to the terminator, not the line
+        assert_eq!(map.line_span(5), Span::new(0, 5));
+        assert_eq!(map.line_span(7), Span::new(7, 13));
+        assert_eq!(map.line_span(13), Span::new(7, 13));
+        assert_eq!(map.line_span(16), Span::new(14, 18));
+        assert_eq!(map.line_span(100), Span::new(14, 18));
+        assert_eq!(map.line_span(16).slice(source), Some("last"));
+    }
+
+    #[test]
+    fn test_line_span_empty_lines() {
+        let map = SourceMap::new("a\n\n");
+        assert_eq!(map.line_span(2), Span::empty_at(2));
+        assert_eq!(map.line_span(3), Span::empty_at(3));
+        assert_eq!(SourceMap::new("").line_span(0), Span::empty_at(0));
+    }
+
+    #[test]
+    fn test_snippet() {
+        let map = SourceMap::new("one\ntwo\nthree\nfour\nfive\n");
+
+        assert_eq!(
+            map.snippet(Span::new(8, 13), 1),
+            vec![(2, "two"), (3, "three"), (4, "four")]
+        );
+        // A span over two lines, ending just after a line terminator
+        assert_eq!(
+            map.snippet(Span::new(5, 14), 0),
+            vec![(2, "two"), (3, "three")]
+        );
+        assert_eq!(map.snippet(Span::empty_at(8), 0), vec![(3, "three")]);
+    }
+
+    #[test]
+    fn test_snippet_clamps_context() {
+        let source = "one\ntwo\nthree\nfour\nfive";
+        let map = SourceMap::new(source);
+
+        assert_eq!(
+            map.snippet(Span::new(0, 3), 2),
+            vec![(1, "one"), (2, "two"), (3, "three")]
+        );
+        assert_eq!(
+            map.snippet(Span::new(19, 23), 2),
+            vec![(3, "three"), (4, "four"), (5, "five")]
+        );
+        assert_eq!(map.snippet(Span::new(8, 13), usize::MAX).len(), 5);
+        assert_eq!(
+            map.snippet(Span::empty_at(source.len() + 10), 0),
+            vec![(5, "five")]
+        );
+    }
+
+    #[test]
+    fn test_line_col_display() {
+        let map = SourceMap::new("a\nb");
+        assert_eq!(map.line_col(2).to_string(), "2:1");
+    }
+
+    #[test]
+    fn test_line_col_tabs() {
+        let map = SourceMap::new("\tx\n  \t y").with_tab_width(4);
+        assert_eq!(map.line_col(1), LineCol { line: 1, col: 5 });
+        // Two spaces then a tab still only reach the first tab stop
+        assert_eq!(map.line_col(7), LineCol { line: 2, col: 6 });
+    }
+
+    #[test]
+    fn test_line_col_tab_width() {
+        let source = " \tval";
+        assert_eq!(SourceMap::new(source).with_tab_width(1).line_col(2).col, 3);
+        assert_eq!(SourceMap::new(source).with_tab_width(8).line_col(2).col, 9);
+        assert_eq!(SourceMap::new(source).line_col(2).col, 5);
+    }
+
+    #[test]
+    fn test_expand_tabs() {
+        let map = SourceMap::new("").with_tab_width(4);
+        assert_eq!(map.expand_tabs("a\tb\t\tc"), "a   b       c");
+    }
+}
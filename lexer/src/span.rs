@@ -1,11 +1,29 @@
 use std::fmt::Display;
+use std::ops::Range;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Identifies a source file when lexing many files.
+/// Single-file users can ignore it, as lexers default to `FileId(0)`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(pub u32);
+
+/// A range of byte offsets in a source file.
+/// Spans order by start, then end, so sorting them puts them in source order.
+/// A span does not record its file, so that single-file use pays nothing for one:
+/// see [`FileSpan`] for a span that does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Span {
     pub start: usize,
     pub end: usize,
 }
 
+/// A span together with the file it refers to, for diagnostics across many files.
+/// Orders by file, then by span.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FileSpan {
+    pub file: FileId,
+    pub span: Span,
+}
+
 impl Span {
     /// Create a new span with the given start and end positions.
     /// Note that the end position is exclusive.
@@ -13,7 +31,23 @@ impl Span {
         Span { start, end }
     }
 
-    /// Get the length of the span.
+    /// Create an empty span at the given position, such as for a missing token.
+    pub fn empty_at(pos: usize) -> Self {
+        Span::new(pos, pos)
+    }
+
+    /// Attach the file the span refers to.
+    pub fn in_file(self, file: FileId) -> FileSpan {
+        FileSpan { file, span: self }
+    }
+
+    /// Get the text the span covers in `source`.
+    /// Returns `None` if the span is out of bounds or does not fall on character boundaries.
+    pub fn slice<'s>(&self, source: &'s str) -> Option<&'s str> {
+        source.get(self.start..self.end)
+    }
+
+    /// Get the length of the span, in bytes.
     pub fn len(&self) -> usize {
         self.end - self.start
     }
@@ -22,6 +56,30 @@ impl Span {
     pub fn is_empty(&self) -> bool {
         self.start == self.end
     }
+
+    /// Extend the end of the span by `n` positions, saturating at `usize::MAX`.
+    pub fn grow(self, n: usize) -> Self {
+        Span {
+            end: self.end.saturating_add(n),
+            ..self
+        }
+    }
+
+    /// Pull the end of the span back by `n` positions, stopping at the start so it never inverts.
+    pub fn shrink(self, n: usize) -> Self {
+        Span {
+            end: self.end.saturating_sub(n).max(self.start),
+            ..self
+        }
+    }
+
+    /// Create the smallest span covering both this span and `other`.
+    pub fn merge(self, other: Span) -> Self {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
 }
 
 impl Display for Span {
@@ -30,6 +88,24 @@ impl Display for Span {
     }
 }
 
+impl Display for FileSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "file {} {}", self.file.0, self.span)
+    }
+}
+
+impl From<Range<usize>> for Span {
+    fn from(range: Range<usize>) -> Self {
+        Span::new(range.start, range.end)
+    }
+}
+
+impl From<Span> for Range<usize> {
+    fn from(span: Span) -> Self {
+        span.start..span.end
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -41,15 +117,107 @@ mod tests {
         assert_eq!(span.end, 10);
     }
 
+    #[test]
+    fn test_span_in_file() {
+        let span = Span::new(5, 10).in_file(FileId(2));
+        assert_eq!(span.file, FileId(2));
+        assert_eq!(span.span, Span::new(5, 10));
+        assert_eq!(span.to_string(), "file 2 [5..10]");
+
+        // Spans in an earlier file sort first, wherever they are in it
+        assert!(Span::new(0, 1).in_file(FileId(2)) > Span::new(8, 9).in_file(FileId(1)));
+    }
+
     #[test]
     fn test_span_length() {
         let span = Span::new(5, 10);
         assert_eq!(span.len(), 5);
     }
 
+    #[test]
+    fn test_span_slice() {
+        let source = "caf\u{e9} bar";
+        assert_eq!(Span::new(6, 9).slice(source), Some("bar"));
+        assert_eq!(Span::new(0, 5).slice(source), Some("caf\u{e9}"));
+        // Inside the two bytes of the accented character
+        assert_eq!(Span::new(0, 4).slice(source), None);
+        assert_eq!(Span::new(6, 20).slice(source), None);
+    }
+
+    #[test]
+    fn test_span_ordering() {
+        let mut spans = vec![
+            Span::new(4, 9),
+            Span::new(0, 3),
+            Span::new(4, 5),
+            Span::new(10, 10),
+            Span::new(0, 1),
+            Span::new(4, 5),
+        ];
+        spans.sort();
+
+        assert_eq!(
+            spans,
+            vec![
+                Span::new(0, 1),
+                Span::new(0, 3),
+                Span::new(4, 5),
+                Span::new(4, 5),
+                Span::new(4, 9),
+                Span::new(10, 10),
+            ]
+        );
+    }
+
     #[test]
     fn test_span_display() {
         let span = Span::new(5, 10);
         assert_eq!(format!("{}", span), "[5..10]");
     }
+
+    #[test]
+    fn test_span_empty_at() {
+        let span = Span::empty_at(4);
+        assert_eq!(span, Span::new(4, 4));
+        assert!(span.is_empty());
+    }
+
+    #[test]
+    fn test_span_grow() {
+        assert_eq!(Span::new(2, 5).grow(3), Span::new(2, 8));
+        assert_eq!(
+            Span::new(2, usize::MAX - 1).grow(5),
+            Span::new(2, usize::MAX)
+        );
+    }
+
+    #[test]
+    fn test_span_shrink() {
+        assert_eq!(Span::new(2, 8).shrink(3), Span::new(2, 5));
+        assert_eq!(Span::new(2, 5).shrink(10), Span::new(2, 2));
+        assert_eq!(Span::empty_at(3).shrink(1), Span::empty_at(3));
+    }
+
+    #[test]
+    fn test_span_merge() {
+        let a = Span::new(2, 5);
+        let b = Span::new(8, 10);
+        assert_eq!(a.merge(b), Span::new(2, 10));
+        assert_eq!(b.merge(a), Span::new(2, 10));
+        assert_eq!(a.merge(Span::new(3, 4)), a);
+    }
+
+    #[test]
+    fn test_span_from_range() {
+        let span = Span::from(3..7);
+        assert_eq!(span, Span::new(3, 7));
+    }
+
+    #[test]
+    fn test_span_range_round_trip() {
+        let span = Span::new(3, 7);
+        let range: Range<usize> = span.into();
+        assert_eq!(range, 3..7);
+        assert_eq!(Span::from(range), span);
+    }
 }
@@ -0,0 +1,89 @@
+use crate::error::LexError;
+use crate::tokens::{Token, TokenCategory, TokenKind};
+
+/// A summary of a lexed input: how many tokens of each category it holds, and how many errors.
+/// `Eof` is not counted, as it stands for no input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LexStats {
+    /// Number of tokens of any category.
+    pub tokens: usize,
+    pub keywords: usize,
+    pub identifiers: usize,
+    pub literals: usize,
+    pub operators: usize,
+    pub delimiters: usize,
+    /// Number of whitespace and comment tokens, when they were emitted.
+    pub trivia: usize,
+    /// Number of comments, which are also counted as trivia.
+    /// Ordinary comments are only counted if they were emitted.
+    pub comments: usize,
+    pub errors: usize,
+}
+
+impl LexStats {
+    /// Compute the statistics of a completed lex, such as the result of [`crate::tokenize_all`].
+    pub fn new(tokens: &[Token], errors: &[LexError]) -> Self {
+        let mut stats = LexStats {
+            errors: errors.len(),
+            ..LexStats::default()
+        };
+        for token in tokens.iter().filter(|token| !token.is_eof()) {
+            stats.tokens += 1;
+            let count = match token.kind.category() {
+                TokenCategory::Keyword => &mut stats.keywords,
+                TokenCategory::Identifier => &mut stats.identifiers,
+                TokenCategory::Literal => &mut stats.literals,
+                TokenCategory::Operator => &mut stats.operators,
+                TokenCategory::Delimiter => &mut stats.delimiters,
+                TokenCategory::Trivia => &mut stats.trivia,
+            };
+            *count += 1;
+            if matches!(token.kind, TokenKind::DocComment(_) | TokenKind::Comment(_)) {
+                stats.comments += 1;
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let input = "/// Doubles.\nfn double(x: int): int {\n    x * 2 $ \"done\"\n}";
+        let (tokens, errors) = crate::tokenize_all(input);
+
+        assert_eq!(
+            LexStats::new(&tokens, &errors),
+            LexStats {
+                tokens: 16,
+                keywords: 1,
+                identifiers: 5,
+                literals: 2,
+                operators: 1,
+                delimiters: 6,
+                trivia: 1,
+                comments: 1,
+                errors: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_stats_with_trivia() {
+        let tokens = crate::tokenize_with_trivia("a // one\n/// two").unwrap();
+        let stats = LexStats::new(&tokens, &[]);
+
+        assert_eq!(stats.tokens, 5);
+        assert_eq!(stats.trivia, 4);
+        assert_eq!(stats.comments, 2);
+    }
+
+    #[test]
+    fn test_stats_empty() {
+        let (tokens, errors) = crate::tokenize_all("");
+        assert_eq!(LexStats::new(&tokens, &errors), LexStats::default());
+    }
+}
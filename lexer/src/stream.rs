@@ -0,0 +1,805 @@
+use std::str::FromStr;
+
+use crate::error::{LexError, ParseError};
+use crate::intern::Interner;
+use crate::span::Span;
+use crate::tokens::{Associativity, Token, TokenKind};
+
+/// Get the span enclosing a sequence of tokens, from the start of the first to the end of the last.
+/// Returns `None` if there are no tokens.
+pub fn span_of(tokens: &[Token]) -> Option<Span> {
+    let first = tokens.first()?;
+    let last = tokens.last()?;
+    Some(first.pos.merge(last.pos))
+}
+
+/// Get the kinds of a sequence of tokens, for comparing tokens without their positions.
+pub fn kinds(tokens: &[Token]) -> Vec<TokenKind> {
+    tokens.iter().map(|token| token.kind.clone()).collect()
+}
+
+/// Assert that tokens have the expected kinds, including any payloads.
+/// On a mismatch, panics naming the first index that differs,
+/// and shows the tokens around it from both sequences so the divergence is easy to spot.
+#[cfg(test)]
+#[track_caller]
+pub(crate) fn assert_token_kinds(actual: &[Token], expected: &[TokenKind]) {
+    /// Number of tokens shown on either side of the first difference.
+    const CONTEXT: usize = 2;
+
+    let actual = kinds(actual);
+    let Some(index) = (0..actual.len().max(expected.len()))
+        .find(|&index| actual.get(index) != expected.get(index))
+    else {
+        return;
+    };
+
+    let describe =
+        |kind: Option<&TokenKind>| kind.map_or("nothing".to_string(), |kind| format!("{:?}", kind));
+    let window = |kinds: &[TokenKind]| {
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(kinds.len());
+        let mut parts: Vec<_> = (start..end)
+            .map(|i| match &kinds[i] {
+                kind if i == index => format!("[{}]", kind),
+                kind => kind.to_string(),
+            })
+            .collect();
+        if index >= kinds.len() {
+            parts.push("[]".to_string());
+        }
+        let before = if start > 0 { "... " } else { "" };
+        let after = if end < kinds.len() { " ..." } else { "" };
+        format!("{}{}{}", before, parts.join(" "), after)
+    };
+    panic!(
+        "token kinds differ at index {}: expected {}, found {}\n  expected: {}\n  actual:   {}",
+        index,
+        describe(expected.get(index)),
+        describe(actual.get(index)),
+        window(expected),
+        window(&actual)
+    );
+}
+
+/// Rebuild source text by joining the text each token's span covers in `source`.
+/// For the tokens of [`crate::tokenize_with_trivia`] this reproduces `source` exactly.
+/// Returns `None` if a span is out of range or splits a character, or if a token does not start
+/// where the one before it ends, as any of these reveals a span bug.
+pub fn reconstruct(tokens: &[Token], source: &str) -> Option<String> {
+    let mut text = String::with_capacity(source.len());
+    for (index, token) in tokens.iter().enumerate() {
+        if index > 0 && tokens[index - 1].pos.end != token.pos.start {
+            return None;
+        }
+        text.push_str(token.pos.slice(source)?);
+    }
+    Some(text)
+}
+
+/// Format tokens one per line for debugging, as in `0  val  [0..3]`.
+/// Each line is numbered, and the kinds are padded so that the spans line up.
+/// Interned identifiers are shown by name if their `interner` is given, and by symbol otherwise.
+pub fn dump_tokens(tokens: &[Token], interner: Option<&Interner>) -> String {
+    let kinds: Vec<_> = tokens
+        .iter()
+        .map(|token| match (&token.kind, interner) {
+            (TokenKind::InternedIdentifier(symbol), Some(interner)) => interner
+                .resolve(*symbol)
+                .map_or_else(|| token.kind.to_string(), str::to_string),
+            (kind, _) => kind.to_string(),
+        })
+        .collect();
+    let index_width = tokens.len().saturating_sub(1).to_string().len();
+    let kind_width = kinds
+        .iter()
+        .map(|kind| kind.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut output = String::new();
+    for (index, (token, kind)) in tokens.iter().zip(&kinds).enumerate() {
+        output.push_str(&format!(
+            "{:>index_width$}  {:<kind_width$}  {}\n",
+            index, kind, token.pos
+        ));
+    }
+    output
+}
+
+/// A sequence of tokens produced by lexing a whole input, ending with `Eof`.
+/// Keeps a cursor over the tokens for a parser to consume them one at a time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenStream {
+    tokens: Vec<Token>,
+    /// Index of the next token to be consumed.
+    position: usize,
+}
+
+impl TokenStream {
+    /// Create a stream from already lexed tokens, with the cursor at the first.
+    pub fn new(tokens: Vec<Token>) -> Self {
+        TokenStream {
+            tokens,
+            position: 0,
+        }
+    }
+
+    /// Get the tokens in the stream, including any already consumed.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// Get the next token without consuming it.
+    /// Returns `None` if every token has been consumed.
+    pub fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// Consume and return the next token.
+    /// A final `Eof` is never consumed, so it is returned by every call once reached.
+    pub fn advance(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position)?;
+        if !token.is_eof() {
+            self.position += 1;
+        }
+        Some(token)
+    }
+
+    /// Split a `>>` at the cursor into two `>` tokens, each spanning one of its characters.
+    /// A parser closing nested type arguments, as in `Vec<Vec<int>>`, can then consume just one.
+    /// Returns whether the next token was split.
+    pub fn split_gt(&mut self) -> bool {
+        let Some(token) = self.tokens.get_mut(self.position) else {
+            return false;
+        };
+        if token.kind != TokenKind::ShiftRight {
+            return false;
+        }
+        let middle = token.pos.start + 1;
+        let second = Token {
+            kind: TokenKind::GreaterThan,
+            pos: Span {
+                start: middle,
+                ..token.pos
+            },
+        };
+        token.kind = TokenKind::GreaterThan;
+        token.pos.end = middle;
+        self.tokens.insert(self.position + 1, second);
+        true
+    }
+
+    /// Consume the next token if it has the given kind, including any payload.
+    /// Returns whether a token was consumed.
+    pub fn eat(&mut self, kind: &TokenKind) -> bool {
+        let matches = self.peek().is_some_and(|token| token.is(kind));
+        if matches {
+            self.advance();
+        }
+        matches
+    }
+
+    /// Consume the next token, which must have the given kind, including any payload.
+    /// Returns an error holding the token found instead, which is left unconsumed.
+    pub fn expect(&mut self, kind: &TokenKind) -> Result<Token, ParseError> {
+        match self.peek() {
+            Some(token) if token.is(kind) => {
+                let token = token.clone();
+                self.advance();
+                Ok(token)
+            }
+            Some(token) => Err(ParseError::UnexpectedToken {
+                expected: kind.clone(),
+                found: token.kind.clone(),
+                span: token.pos,
+            }),
+            // Running out of tokens is treated as reaching the end of the input
+            None => Err(ParseError::UnexpectedToken {
+                expected: kind.clone(),
+                found: TokenKind::Eof,
+                span: self
+                    .tokens
+                    .last()
+                    .map_or(Span::empty_at(0), |token| Span::empty_at(token.pos.end)),
+            }),
+        }
+    }
+
+    /// Parse one expression at the cursor, consuming it, and return its span.
+    /// A minimal Pratt parser driven by [`TokenKind::infix_binding`], which checks the structure
+    /// of the expression without building a tree. Comparisons are non-associative, so
+    /// `a == b == c` is an error at the second comparison, while `(a == b) == c` is accepted.
+    /// Returns an error if an operand or closing bracket is missing.
+    pub fn parse_expression(&mut self) -> Result<Span, ParseError> {
+        self.parse_binary(0)
+    }
+
+    /// Parse an expression whose operators bind at least as tightly as `min_power`.
+    fn parse_binary(&mut self, min_power: u8) -> Result<Span, ParseError> {
+        let mut span = self.parse_operand()?;
+        // The binding power of a non-associative operator applied last at this level
+        let mut non_associative = None;
+        while let Some((power, associativity)) =
+            self.peek().and_then(|token| token.kind.infix_binding())
+        {
+            if power < min_power {
+                break;
+            }
+            let operator = self.current().clone();
+            if associativity == Associativity::NonAssociative && non_associative == Some(power) {
+                return Err(ParseError::ChainedComparison {
+                    operator: operator.kind,
+                    span: operator.pos,
+                });
+            }
+            self.advance();
+            let right_power = match associativity {
+                Associativity::Right => power,
+                Associativity::Left | Associativity::NonAssociative => power + 1,
+            };
+            span = span.merge(self.parse_binary(right_power)?);
+            non_associative = (associativity == Associativity::NonAssociative).then_some(power);
+        }
+        Ok(span)
+    }
+
+    /// Parse an operand: a prefix operator applied to an operand, a name, a literal,
+    /// or a bracketed list of expressions, followed by any calls, indexing and `?`.
+    fn parse_operand(&mut self) -> Result<Span, ParseError> {
+        let token = self.current().clone();
+        let mut span = match token.kind {
+            TokenKind::Minus | TokenKind::Not => {
+                self.advance();
+                token.pos.merge(self.parse_binary(PREFIX_BINDING)?)
+            }
+            TokenKind::LParen => self.parse_group(&TokenKind::RParen)?,
+            TokenKind::LBracket => self.parse_group(&TokenKind::RBracket)?,
+            ref kind if is_atom(kind) => {
+                self.advance();
+                token.pos
+            }
+            found => {
+                return Err(ParseError::ExpectedExpression {
+                    found,
+                    span: token.pos,
+                });
+            }
+        };
+        loop {
+            let next = self.current();
+            span = match next.kind {
+                TokenKind::LParen => span.merge(self.parse_group(&TokenKind::RParen)?),
+                TokenKind::LBracket => span.merge(self.parse_group(&TokenKind::RBracket)?),
+                TokenKind::Question => {
+                    let question = next.pos;
+                    self.advance();
+                    span.merge(question)
+                }
+                _ => return Ok(span),
+            };
+        }
+    }
+
+    /// Parse a bracket at the cursor holding comma-separated expressions, up to `close`.
+    fn parse_group(&mut self, close: &TokenKind) -> Result<Span, ParseError> {
+        let open = self.current().pos;
+        self.advance();
+        loop {
+            if let Some(token) = self.peek().filter(|token| token.is(close)) {
+                let end = token.pos;
+                self.advance();
+                return Ok(open.merge(end));
+            }
+            self.parse_expression()?;
+            if !self.eat(&TokenKind::Comma) {
+                let end = self.expect(close)?.pos;
+                return Ok(open.merge(end));
+            }
+        }
+    }
+
+    /// Skip a type at the cursor, such as `int`, `Vec<Vec<int>>` or `int?`.
+    /// Returns whether a whole type was skipped.
+    fn skip_type(&mut self) -> bool {
+        if !self.peek().is_some_and(Token::is_identifier) {
+            return false;
+        }
+        self.advance();
+        if self.eat(&TokenKind::LessThan) {
+            loop {
+                if !self.skip_type() {
+                    return false;
+                }
+                if !self.eat(&TokenKind::Comma) {
+                    break;
+                }
+            }
+            // The `>>` closing nested arguments is lexed as a shift
+            self.split_gt();
+            if !self.eat(&TokenKind::GreaterThan) {
+                return false;
+            }
+        }
+        self.eat(&TokenKind::Question);
+        true
+    }
+
+    /// Get the token at the cursor, treating running out of tokens as reaching `Eof`.
+    fn current(&self) -> Token {
+        self.peek().cloned().unwrap_or_else(|| {
+            let end = self.tokens.last().map_or(0, |token| token.pos.end);
+            Token::new(TokenKind::Eof, end, 0)
+        })
+    }
+
+    /// Check every expression in the stream for a comparison following another, as in
+    /// `a == b == c`, which must be written as `(a == b) == c` or `a == b && b == c`.
+    /// Each expression ends where no operator continues it, so statements on separate lines
+    /// are checked separately, and types after a `:` are skipped, so `Vec<Vec<int>>` is not
+    /// mistaken for comparisons. Anything that does not parse as an expression is skipped.
+    /// Returns an error at the span of the second comparison.
+    pub fn check_comparison_chain(&self) -> Result<(), ParseError> {
+        let mut stream = TokenStream::new(self.tokens.clone());
+        while let Some(token) = stream.peek() {
+            let start = stream.position;
+            match &token.kind {
+                TokenKind::Eof => break,
+                TokenKind::Colon => {
+                    stream.advance();
+                    if !stream.skip_type() {
+                        stream.position = start + 1;
+                    }
+                }
+                kind if starts_operand(kind) => match stream.parse_expression() {
+                    Ok(_) => {}
+                    Err(error @ ParseError::ChainedComparison { .. }) => return Err(error),
+                    Err(_) => stream.position = start + 1,
+                },
+                _ => {
+                    stream.advance();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Take the tokens out of the stream.
+    pub fn into_tokens(self) -> Vec<Token> {
+        self.tokens
+    }
+}
+
+/// Binding power of the prefix operators, so that `-a * b` negates `a` alone,
+/// while `-a ^ b` negates `a ^ b`, as exponentiation binds more tightly still.
+const PREFIX_BINDING: u8 = 8;
+
+/// Check if a token can start an operand.
+fn starts_operand(kind: &TokenKind) -> bool {
+    is_atom(kind)
+        || matches!(
+            kind,
+            TokenKind::Minus | TokenKind::Not | TokenKind::LParen | TokenKind::LBracket
+        )
+}
+
+/// Check if a token is a whole operand on its own: a name or a literal.
+fn is_atom(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Identifier(_)
+            | TokenKind::InternedIdentifier(_)
+            | TokenKind::Underscore
+            | TokenKind::IntLiteral(_)
+            | TokenKind::BigIntLiteral(_)
+            | TokenKind::StringLiteral(_)
+            | TokenKind::CharLiteral(_)
+    )
+}
+
+impl FromStr for TokenStream {
+    type Err = LexError;
+
+    /// Lex the whole string, stopping at the first error.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::tokenize(s).map(TokenStream::new)
+    }
+}
+
+impl From<TokenStream> for Vec<Token> {
+    fn from(stream: TokenStream) -> Self {
+        stream.tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stream() {
+        let stream: TokenStream = "val x = 5".parse().unwrap();
+        assert_eq!(
+            stream.into_tokens(),
+            vec![
+                Token::new(TokenKind::Val, 0, 3),
+                Token::new(TokenKind::Identifier("x".to_string()), 4, 1),
+                Token::new(TokenKind::Assign, 6, 1),
+                Token::new(TokenKind::IntLiteral(5), 8, 1),
+                Token::new(TokenKind::Eof, 9, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_stream_error() {
+        let result = "x | y".parse::<TokenStream>();
+        assert_eq!(
+            result,
+            Err(LexError::ExpectedOneOf {
+                ch: '|',
+                expected: &["||"],
+                span: Span::new(2, 3),
+            })
+        );
+    }
+
+    #[test]
+    fn test_advance_and_peek() {
+        let mut stream: TokenStream = "a + b".parse().unwrap();
+
+        assert_eq!(stream.peek().unwrap().as_identifier(), Some("a"));
+        assert_eq!(stream.advance().unwrap().as_identifier(), Some("a"));
+        assert!(stream.advance().unwrap().is(&TokenKind::Plus));
+        assert_eq!(stream.peek().unwrap().as_identifier(), Some("b"));
+        stream.advance();
+
+        // The cursor stays on `Eof` once it gets there
+        assert!(stream.advance().unwrap().is_eof());
+        assert!(stream.advance().unwrap().is_eof());
+        assert!(stream.peek().unwrap().is_eof());
+    }
+
+    #[test]
+    fn test_eat() {
+        let mut stream: TokenStream = "( x".parse().unwrap();
+
+        assert!(!stream.eat(&TokenKind::RParen));
+        assert!(stream.eat(&TokenKind::LParen));
+        assert!(!stream.eat(&TokenKind::Identifier("y".to_string())));
+        assert!(stream.eat(&TokenKind::Identifier("x".to_string())));
+        assert!(stream.eat(&TokenKind::Eof));
+        assert!(stream.peek().unwrap().is_eof());
+    }
+
+    #[test]
+    fn test_expect() {
+        let mut stream: TokenStream = "f(a, b)".parse().unwrap();
+        stream.advance();
+
+        assert_eq!(
+            stream.expect(&TokenKind::LParen),
+            Ok(Token::new(TokenKind::LParen, 1, 1))
+        );
+        stream.advance();
+        assert_eq!(
+            stream.expect(&TokenKind::RParen),
+            Err(ParseError::UnexpectedToken {
+                expected: TokenKind::RParen,
+                found: TokenKind::Comma,
+                span: Span::new(3, 4),
+            })
+        );
+        // The mismatched token is left for the parser to handle
+        assert!(stream.eat(&TokenKind::Comma));
+    }
+
+    #[test]
+    fn test_split_gt() {
+        let mut stream: TokenStream = "Vec<Vec<int>> x".parse().unwrap();
+        assert_eq!(
+            kinds(stream.tokens()),
+            vec![
+                TokenKind::Identifier("Vec".to_string()),
+                TokenKind::LessThan,
+                TokenKind::Identifier("Vec".to_string()),
+                TokenKind::LessThan,
+                TokenKind::Identifier("int".to_string()),
+                TokenKind::ShiftRight,
+                TokenKind::Identifier("x".to_string()),
+                TokenKind::Eof,
+            ]
+        );
+        for _ in 0..5 {
+            stream.advance();
+        }
+
+        assert!(stream.split_gt());
+        assert_eq!(
+            stream.expect(&TokenKind::GreaterThan),
+            Ok(Token::new(TokenKind::GreaterThan, 11, 1))
+        );
+        assert_eq!(
+            stream.expect(&TokenKind::GreaterThan),
+            Ok(Token::new(TokenKind::GreaterThan, 12, 1))
+        );
+        assert_eq!(stream.peek().unwrap().as_identifier(), Some("x"));
+    }
+
+    #[test]
+    fn test_split_gt_other_tokens() {
+        let mut stream: TokenStream = "a >> b".parse().unwrap();
+
+        // Only a `>>` at the cursor is split, so a shift elsewhere is left alone
+        assert!(!stream.split_gt());
+        stream.advance();
+        assert!(stream.eat(&TokenKind::ShiftRight));
+        stream.advance();
+        assert!(!stream.split_gt());
+        assert!(stream.peek().unwrap().is_eof());
+    }
+
+    #[test]
+    fn test_expect_past_end() {
+        let mut stream = TokenStream::new(vec![Token::new(TokenKind::Val, 0, 3)]);
+        stream.advance();
+
+        assert_eq!(
+            stream.expect(&TokenKind::Val),
+            Err(ParseError::UnexpectedToken {
+                expected: TokenKind::Val,
+                found: TokenKind::Eof,
+                span: Span::new(3, 3),
+            })
+        );
+        assert_eq!(stream.advance(), None);
+    }
+
+    #[test]
+    fn test_parse_expression() {
+        let mut stream: TokenStream = "-a ^ b * f(c, [d])[0]? + (e) next".parse().unwrap();
+        assert_eq!(stream.parse_expression(), Ok(Span::new(0, 28)));
+        // The expression ends where no operator continues it
+        assert_eq!(stream.peek().unwrap().as_identifier(), Some("next"));
+
+        let mut stream: TokenStream = "(a == b) == c".parse().unwrap();
+        assert_eq!(stream.parse_expression(), Ok(Span::new(0, 13)));
+    }
+
+    #[test]
+    fn test_parse_expression_errors() {
+        let mut stream: TokenStream = "a + )".parse().unwrap();
+        assert_eq!(
+            stream.parse_expression(),
+            Err(ParseError::ExpectedExpression {
+                found: TokenKind::RParen,
+                span: Span::new(4, 5),
+            })
+        );
+
+        let mut stream: TokenStream = "f(a b)".parse().unwrap();
+        assert_eq!(
+            stream.parse_expression(),
+            Err(ParseError::UnexpectedToken {
+                expected: TokenKind::RParen,
+                found: TokenKind::Identifier("b".to_string()),
+                span: Span::new(4, 5),
+            })
+        );
+
+        let mut stream: TokenStream = "a ==".parse().unwrap();
+        assert_eq!(
+            stream.parse_expression(),
+            Err(ParseError::ExpectedExpression {
+                found: TokenKind::Eof,
+                span: Span::new(4, 4),
+            })
+        );
+    }
+
+    #[test]
+    fn test_chained_comparison_rejected() {
+        let stream: TokenStream = "a == b == c".parse().unwrap();
+        assert_eq!(
+            stream.check_comparison_chain(),
+            Err(ParseError::ChainedComparison {
+                operator: TokenKind::Equals,
+                span: Span::new(7, 9),
+            })
+        );
+
+        // Chains are still found inside brackets, and after a type
+        let stream: TokenStream = "val v: Vec<int> = f(a < b < c)".parse().unwrap();
+        assert_eq!(
+            stream.check_comparison_chain(),
+            Err(ParseError::ChainedComparison {
+                operator: TokenKind::LessThan,
+                span: Span::new(26, 27),
+            })
+        );
+
+        // Arithmetic between the comparisons does not separate them
+        let stream: TokenStream = "f(x) < y + 1 >= z".parse().unwrap();
+        assert_eq!(
+            stream.check_comparison_chain(),
+            Err(ParseError::ChainedComparison {
+                operator: TokenKind::GreaterThanOrEqual,
+                span: Span::new(13, 15),
+            })
+        );
+    }
+
+    #[test]
+    fn test_separate_comparisons_accepted() {
+        for input in [
+            "(a == b) == c",
+            "a == (b == c)",
+            "a == b && b == c",
+            "a < b || !(c > d)",
+            "f(a == b, c != d) == e",
+            "val x = a == b\nval y = c == d",
+            "a == b\nc == d",
+            "{ a == b\n c == d }",
+            "val v: Vec<Vec<int>> = x",
+            "fn f(a: Map<int, Vec<int>>, b: int?): bool { a < b }",
+        ] {
+            let stream: TokenStream = input.parse().unwrap();
+            assert_eq!(stream.check_comparison_chain(), Ok(()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_kinds_ignore_positions() {
+        let compact: TokenStream = "f(a,b)".parse().unwrap();
+        let spaced: TokenStream = "f ( a , b )  // call".parse().unwrap();
+
+        assert_ne!(compact.tokens(), spaced.tokens());
+        assert_eq!(kinds(compact.tokens()), kinds(spaced.tokens()));
+        assert_eq!(
+            kinds(&compact.tokens()[1..3]),
+            vec![TokenKind::LParen, TokenKind::Identifier("a".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_assert_token_kinds() {
+        let tokens = crate::tokenize("f(a, b)").unwrap();
+        assert_token_kinds(
+            &tokens,
+            &[
+                TokenKind::Identifier("f".to_string()),
+                TokenKind::LParen,
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Comma,
+                TokenKind::Identifier("b".to_string()),
+                TokenKind::RParen,
+                TokenKind::Eof,
+            ],
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "token kinds differ at index 5: expected Minus, found Plus\n  \
+                               expected: ... ( a [-] b ) ...\n  \
+                               actual:   ... ( a [+] b ) ..."
+    )]
+    fn test_assert_token_kinds_mismatch() {
+        let tokens = crate::tokenize("x + f(a + b) * 2").unwrap();
+        let mut expected = kinds(&tokens);
+        expected[5] = TokenKind::Minus;
+        assert_token_kinds(&tokens, &expected);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "token kinds differ at index 2: expected Eof, found nothing\n  \
+                               expected: a + [EOF]\n  \
+                               actual:   a + []"
+    )]
+    fn test_assert_token_kinds_too_short() {
+        let tokens = crate::tokenize("a +").unwrap();
+        assert_token_kinds(
+            &tokens[..2],
+            &[
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Plus,
+                TokenKind::Eof,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_reconstruct() {
+        let source = "f(a) // call";
+        let tokens = crate::tokenize_with_trivia(source).unwrap();
+        assert_eq!(reconstruct(&tokens, source).as_deref(), Some(source));
+
+        // Without trivia, there are gaps between the tokens
+        let tokens = crate::tokenize(source).unwrap();
+        assert_eq!(reconstruct(&tokens, source), None);
+        assert_eq!(reconstruct(&tokens[..4], source).as_deref(), Some("f(a)"));
+    }
+
+    #[test]
+    fn test_reconstruct_bad_spans() {
+        let source = "f(\u{e9})";
+        let tokens = [
+            Token::new(TokenKind::Identifier("f".to_string()), 0, 1),
+            Token::new(TokenKind::LParen, 1, 1),
+        ];
+        assert_eq!(reconstruct(&tokens, source).as_deref(), Some("f("));
+
+        // Overlapping spans
+        let overlapping = [tokens[0].clone(), Token::new(TokenKind::LParen, 0, 2)];
+        assert_eq!(reconstruct(&overlapping, source), None);
+        // A span ending inside a character
+        let split = [
+            tokens[1].clone(),
+            Token::new(TokenKind::CharLiteral('\u{e9}'), 2, 1),
+        ];
+        assert_eq!(reconstruct(&split, source), None);
+        // A span past the end of the source
+        let past_end = [Token::new(TokenKind::Eof, 5, 1)];
+        assert_eq!(reconstruct(&past_end, source), None);
+    }
+
+    #[test]
+    fn test_dump_tokens() {
+        let stream: TokenStream = "val total = f(10)\nval s = \"hi\"".parse().unwrap();
+
+        assert_eq!(
+            dump_tokens(stream.tokens(), None),
+            " 0  val    [0..3]\n \
+             1  total  [4..9]\n \
+             2  =      [10..11]\n \
+             3  f      [12..13]\n \
+             4  (      [13..14]\n \
+             5  10     [14..16]\n \
+             6  )      [16..17]\n \
+             7  val    [18..21]\n \
+             8  s      [22..23]\n \
+             9  =      [24..25]\n\
+             10  \"hi\"   [26..30]\n\
+             11  EOF    [30..30]\n"
+        );
+    }
+
+    #[test]
+    fn test_dump_no_tokens() {
+        assert_eq!(dump_tokens(&[], None), "");
+    }
+
+    #[test]
+    fn test_dump_interned_tokens() {
+        let (tokens, interner) = crate::tokenize_interned("count + total").unwrap();
+
+        assert_eq!(
+            dump_tokens(&tokens, Some(&interner)),
+            "0  count  [0..5]\n1  +      [6..7]\n2  total  [8..13]\n3  EOF    [13..13]\n"
+        );
+        // Without the interner, only the symbols are known
+        assert_eq!(
+            dump_tokens(&tokens, None),
+            "0  #0   [0..5]\n1  +    [6..7]\n2  #1   [8..13]\n3  EOF  [13..13]\n"
+        );
+    }
+
+    #[test]
+    fn test_span_of_empty() {
+        assert_eq!(span_of(&[]), None);
+    }
+
+    #[test]
+    fn test_span_of_single() {
+        let tokens = [Token::new(TokenKind::Val, 4, 3)];
+        assert_eq!(span_of(&tokens), Some(Span::new(4, 7)));
+    }
+
+    #[test]
+    fn test_span_of_many() {
+        let stream: TokenStream = "(a + b) * c".parse().unwrap();
+        assert_eq!(span_of(&stream.tokens()[..5]), Some(Span::new(0, 7)));
+        assert_eq!(span_of(stream.tokens()), Some(Span::new(0, 11)));
+    }
+}
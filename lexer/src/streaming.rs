@@ -0,0 +1,540 @@
+use std::fmt::Display;
+use std::io::{self, Read};
+
+use crate::error::LexError;
+use crate::intern::Interner;
+use crate::span::{FileId, FileSpan, Span};
+use crate::tokens::Token;
+use crate::{Lexer, LexerOptions};
+
+/// Number of bytes requested from the reader at a time by default.
+pub const DEFAULT_CHUNK_SIZE: usize = 8 * 1024;
+
+/// The furthest the lexer looks past the end of a token, in bytes: four characters of up to four bytes.
+/// A token ending closer than this to the end of the buffer may be incomplete.
+const LOOKAHEAD: usize = 4 * 4;
+
+#[derive(Debug)]
+pub enum StreamError {
+    /// Reading from the source failed.
+    Io(io::Error),
+    /// The source does not form a valid token.
+    Lex(LexError),
+}
+
+impl Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamError::Io(error) => write!(f, "failed to read input: {}", error),
+            StreamError::Lex(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StreamError::Io(error) => Some(error),
+            StreamError::Lex(error) => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for StreamError {
+    fn from(error: io::Error) -> Self {
+        StreamError::Io(error)
+    }
+}
+
+impl From<LexError> for StreamError {
+    fn from(error: LexError) -> Self {
+        StreamError::Lex(error)
+    }
+}
+
+/// A lexer that pulls its input from a reader as it goes, instead of holding the whole source.
+/// Produces the same tokens as [`Lexer`], with spans as byte offsets from the start of the stream.
+/// Invalid UTF-8 is replaced with `U+FFFD`, which is then reported as an unexpected character
+/// spanning the invalid bytes.
+pub struct StreamingLexer<R> {
+    reader: R,
+    /// Lexer over the window of the stream read but not yet consumed.
+    lexer: Lexer<'static>,
+    /// Trailing bytes of an incomplete UTF-8 sequence, waiting for the rest of the character.
+    partial: Vec<u8>,
+    /// Where the decoded text has grown past the stream, as each replacement character is longer
+    /// than the invalid bytes it stands for: the offset in the decoded text just after each
+    /// replacement, and the total growth up to there.
+    replacements: Vec<(usize, usize)>,
+    /// Growth from replacements before the consumed input, which every remaining span follows.
+    settled: usize,
+    chunk_size: usize,
+    /// Whether the reader has been exhausted.
+    eof: bool,
+}
+
+impl<R: Read> StreamingLexer<R> {
+    /// Create a lexer reading from `reader`.
+    pub fn new(reader: R) -> Self {
+        StreamingLexer {
+            reader,
+            lexer: Lexer::new(String::new()),
+            partial: Vec::new(),
+            replacements: Vec::new(),
+            settled: 0,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            eof: false,
+        }
+    }
+
+    /// Set the options controlling which tokens are produced.
+    pub fn with_options(mut self, options: LexerOptions) -> Self {
+        self.lexer.options = options;
+        self
+    }
+
+    /// Set the file that the spans of tokens and errors refer to.
+    pub fn with_file(mut self, file: FileId) -> Self {
+        self.lexer.file = file;
+        self
+    }
+
+    /// Set how many bytes are requested from the reader at a time.
+    /// A size of zero is treated as one.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Attach the stream's file to the span of one of its tokens or errors.
+    pub fn file_span(&self, span: Span) -> FileSpan {
+        self.lexer.file_span(span)
+    }
+
+    /// Get the interner holding the names of interned identifiers.
+    pub fn interner(&self) -> &Interner {
+        self.lexer.interner()
+    }
+
+    /// Take the interner out of the lexer, once lexing is done.
+    pub fn into_interner(self) -> Interner {
+        self.lexer.into_interner()
+    }
+
+    /// Reads from the stream and produces a token.
+    /// Returns an error if reading fails or the input does not form a valid token.
+    /// As with [`Lexer::next_token`], lexing can continue after a lexing error.
+    pub fn next_token(&mut self) -> Result<Token, StreamError> {
+        // Queued tokens are already complete, so nothing more needs to be read for them
+        if self.eof || self.lexer.has_pending() {
+            let result = self.lexer.next_token();
+            return self.finish(result);
+        }
+
+        let mut wanted = self.chunk_size;
+        loop {
+            let state = self.lexer.save_token();
+            let result = self.lexer.next_token();
+
+            // A token near the end of the buffer may continue in the unread input
+            let near_end = self.lexer.position + LOOKAHEAD > self.lexer.input.len();
+            if self.eof || !near_end {
+                return self.finish(result);
+            }
+            self.lexer.restore_state(state);
+            // Reading twice as much each time means a long token is only lexed again
+            // a logarithmic number of times, rather than once per chunk
+            self.fill(wanted)?;
+            wanted = wanted.saturating_mul(2);
+        }
+    }
+
+    /// Moves the spans of a lexed token or error to their offsets in the stream,
+    /// and drops the consumed input once there is enough of it.
+    fn finish(&mut self, result: Result<Token, LexError>) -> Result<Token, StreamError> {
+        let result = match result {
+            Ok(mut token) => {
+                self.locate(&mut token.pos);
+                Ok(token)
+            }
+            Err(mut error) => {
+                self.locate(error.span_mut());
+                Err(StreamError::Lex(error))
+            }
+        };
+
+        // Drop consumed input in bulk rather than shifting the buffer after every token
+        if self.lexer.position >= self.chunk_size {
+            self.lexer.discard_consumed();
+            let base = self.lexer.base;
+            let passed = self
+                .replacements
+                .partition_point(|&(after, _)| after <= base);
+            if let Some(&(_, growth)) = self.replacements[..passed].last() {
+                self.settled = growth;
+            }
+            self.replacements.drain(..passed);
+        }
+        result
+    }
+
+    /// Converts a span of the decoded text into a span of the stream.
+    fn locate(&self, span: &mut Span) {
+        span.start -= self.growth_before(span.start);
+        span.end -= self.growth_before(span.end);
+    }
+
+    /// Get how much longer the decoded text is than the stream, up to `offset` in the decoded text.
+    fn growth_before(&self, offset: usize) -> usize {
+        let passed = self
+            .replacements
+            .partition_point(|&(after, _)| after <= offset);
+        self.replacements[..passed]
+            .last()
+            .map_or(self.settled, |&(_, growth)| growth)
+    }
+
+    /// Reads at least `wanted` more bytes onto the end of the buffer, unless the reader runs out.
+    fn fill(&mut self, wanted: usize) -> io::Result<()> {
+        let mut chunk = vec![0; self.chunk_size];
+        let mut total = 0;
+        while total < wanted {
+            let read = match self.reader.read(&mut chunk) {
+                Ok(read) => read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            };
+
+            if read == 0 {
+                self.eof = true;
+                // An incomplete character at the end of the stream is invalid
+                if !self.partial.is_empty() {
+                    let len = self.partial.len();
+                    self.partial.clear();
+                    self.push_replacement(len);
+                }
+                return Ok(());
+            }
+
+            total += read;
+            self.partial.extend_from_slice(&chunk[..read]);
+            self.decode();
+        }
+        Ok(())
+    }
+
+    /// Appends a replacement character to the buffer in place of `len` invalid bytes.
+    fn push_replacement(&mut self, len: usize) {
+        let replacement = char::REPLACEMENT_CHARACTER.len_utf8();
+        self.lexer
+            .push_input(char::REPLACEMENT_CHARACTER.encode_utf8(&mut [0; 4]));
+        if len < replacement {
+            let growth = self.growth_before(usize::MAX) + replacement - len;
+            let after = self.lexer.base + self.lexer.input.len();
+            self.replacements.push((after, growth));
+        }
+    }
+
+    /// Moves the complete characters in `partial` into the buffer.
+    fn decode(&mut self) {
+        let mut bytes = std::mem::take(&mut self.partial);
+        let mut rest = &bytes[..];
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    self.lexer.push_input(text);
+                    rest = &[];
+                    break;
+                }
+                Err(error) => {
+                    let (valid, after) = rest.split_at(error.valid_up_to());
+                    // Everything up to `valid_up_to` is valid UTF-8
+                    self.lexer
+                        .push_input(std::str::from_utf8(valid).unwrap_or_default());
+                    match error.error_len() {
+                        Some(len) => {
+                            self.push_replacement(len);
+                            rest = &after[len..];
+                        }
+                        // The sequence may be completed by the next chunk
+                        None => {
+                            rest = after;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        let keep = rest.len();
+        bytes.drain(..bytes.len() - keep);
+        self.partial = bytes;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Span;
+    use crate::tokens::TokenKind;
+
+    /// Lex everything from a streaming lexer, collecting tokens and lexing errors.
+    fn stream_all(
+        input: &str,
+        chunk_size: usize,
+        options: LexerOptions,
+    ) -> Vec<Result<Token, LexError>> {
+        let mut lexer = StreamingLexer::new(input.as_bytes())
+            .with_chunk_size(chunk_size)
+            .with_options(options);
+        let mut results = Vec::new();
+        loop {
+            match lexer.next_token() {
+                Ok(token) => {
+                    let done = token.kind == TokenKind::Eof;
+                    results.push(Ok(token));
+                    if done {
+                        return results;
+                    }
+                }
+                Err(StreamError::Lex(error)) => results.push(Err(error)),
+                Err(StreamError::Io(error)) => panic!("unexpected io error: {}", error),
+            }
+        }
+    }
+
+    /// Lex everything from a streaming lexer over raw bytes, which need not be valid UTF-8.
+    fn stream_all_bytes(input: &[u8], chunk_size: usize) -> Vec<Result<Token, LexError>> {
+        let mut lexer = StreamingLexer::new(input).with_chunk_size(chunk_size);
+        let mut results = Vec::new();
+        loop {
+            match lexer.next_token() {
+                Ok(token) if token.kind == TokenKind::Eof => {
+                    results.push(Ok(token));
+                    return results;
+                }
+                Ok(token) => results.push(Ok(token)),
+                Err(StreamError::Lex(error)) => results.push(Err(error)),
+                Err(StreamError::Io(error)) => panic!("unexpected io error: {}", error),
+            }
+        }
+    }
+
+    /// Lex everything from an in-memory lexer, collecting tokens and errors.
+    fn lex_all(input: &str, options: LexerOptions) -> Vec<Result<Token, LexError>> {
+        let mut lexer = Lexer::from_str(input).with_options(options);
+        let mut results = Vec::new();
+        loop {
+            let result = lexer.next_token();
+            let done = matches!(&result, Ok(token) if token.kind == TokenKind::Eof);
+            results.push(result);
+            if done {
+                return results;
+            }
+        }
+    }
+
+    #[test]
+    fn test_matches_lexer_at_every_chunk_size() {
+        let input = "/// Adds.\r\nfn add(a: int, b: int): int {\n    // sum\n    a + b >= 10 && !c\n}\nval s = \"caf\u{e9} \\\"x\\\"\" $ 12345";
+        let expected = lex_all(input, LexerOptions::default());
+
+        for chunk_size in 1..=9 {
+            assert_eq!(
+                stream_all(input, chunk_size, LexerOptions::default()),
+                expected,
+                "chunk size {}",
+                chunk_size
+            );
+        }
+    }
+
+    #[test]
+    fn test_byte_order_mark() {
+        let input = "\u{FEFF}val x = 5";
+        let expected = lex_all(input, LexerOptions::default());
+
+        for chunk_size in 1..=4 {
+            assert_eq!(
+                stream_all(input, chunk_size, LexerOptions::default()),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_operator_split_across_reads() {
+        let results = stream_all("a == b != c", 1, LexerOptions::default());
+        let kinds: Vec<_> = results
+            .into_iter()
+            .map(|result| result.unwrap().kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Equals,
+                TokenKind::Identifier("b".to_string()),
+                TokenKind::NotEquals,
+                TokenKind::Identifier("c".to_string()),
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_absolute_spans() {
+        let input = "value + other ".repeat(50);
+        let expected = lex_all(&input, LexerOptions::default());
+        let results = stream_all(&input, 3, LexerOptions::default());
+
+        assert_eq!(results, expected);
+        let last = results.last().unwrap().as_ref().unwrap();
+        assert_eq!(last.pos, Span::new(700, 700));
+    }
+
+    #[test]
+    fn test_buffer_stays_bounded() {
+        let input = "value + other\n".repeat(1_000);
+        let mut lexer = StreamingLexer::new(input.as_bytes()).with_chunk_size(16);
+
+        while lexer.next_token().unwrap().kind != TokenKind::Eof {
+            assert!(lexer.lexer.input.len() < 64);
+        }
+    }
+
+    #[test]
+    fn test_modes_match_lexer() {
+        let input = "a:\n    b\n\n    \"multi\nline\"\n  c( // note\n)\n";
+        for options in [
+            LexerOptions {
+                indentation: true,
+                ..LexerOptions::default()
+            },
+            LexerOptions {
+                emit_whitespace: true,
+                emit_comments: true,
+                ..LexerOptions::default()
+            },
+            LexerOptions {
+                intern_identifiers: true,
+                ..LexerOptions::default()
+            },
+            LexerOptions {
+                newlines: true,
+                ..LexerOptions::default()
+            },
+        ] {
+            let expected = lex_all(input, options);
+            for chunk_size in [1, 2, 5] {
+                assert_eq!(stream_all(input, chunk_size, options), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_interner_not_polluted_by_retries() {
+        let options = LexerOptions {
+            intern_identifiers: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = StreamingLexer::new("counter total".as_bytes())
+            .with_chunk_size(2)
+            .with_options(options);
+        while lexer.next_token().unwrap().kind != TokenKind::Eof {}
+
+        assert_eq!(lexer.interner().len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let input: &[u8] = b"a \xff b";
+        let mut lexer = StreamingLexer::new(input).with_chunk_size(1);
+
+        assert_eq!(
+            lexer.next_token().unwrap().kind,
+            TokenKind::Identifier("a".to_string())
+        );
+        match lexer.next_token() {
+            Err(StreamError::Lex(LexError::UnexpectedChar { ch, span })) => {
+                assert_eq!(ch, char::REPLACEMENT_CHARACTER);
+                // The span covers the invalid byte, not the longer replacement character
+                assert_eq!(span, Span::new(2, 3));
+            }
+            other => panic!("expected an unexpected character, got {:?}", other),
+        }
+        assert_eq!(
+            lexer.next_token().unwrap(),
+            Token::new(TokenKind::Identifier("b".to_string()), 4, 1)
+        );
+    }
+
+    #[test]
+    fn test_spans_after_invalid_utf8() {
+        // A one byte and a two byte invalid sequence, then a truncated character at the end
+        let input: &[u8] = b"a\xff b \xe2\x82 cd\n\"end\" \xe2\x82";
+        for chunk_size in [1, 2, 3, 64] {
+            let spans: Vec<_> = stream_all_bytes(input, chunk_size)
+                .into_iter()
+                .map(|result| match result {
+                    Ok(token) => token.pos,
+                    Err(error) => error.span(),
+                })
+                .collect();
+
+            assert_eq!(
+                spans,
+                vec![
+                    Span::new(0, 1),
+                    Span::new(1, 2),
+                    Span::new(3, 4),
+                    Span::new(5, 7),
+                    Span::new(8, 10),
+                    Span::new(11, 16),
+                    Span::new(17, 19),
+                    Span::new(19, 19),
+                ],
+                "chunk size {}",
+                chunk_size
+            );
+        }
+    }
+
+    #[test]
+    fn test_token_much_longer_than_chunk() {
+        let input = format!("val s = \"{}\" + x", "long ".repeat(200_000));
+        let expected = lex_all(&input, LexerOptions::default());
+
+        assert_eq!(stream_all(&input, 16, LexerOptions::default()), expected);
+    }
+
+    #[test]
+    fn test_truncated_utf8_at_eof() {
+        // The first two bytes of a three byte character
+        let input: &[u8] = b"a \xe2\x82";
+        let mut lexer = StreamingLexer::new(input).with_chunk_size(1);
+
+        lexer.next_token().unwrap();
+        assert!(matches!(
+            lexer.next_token(),
+            Err(StreamError::Lex(LexError::UnexpectedChar { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_io_error() {
+        struct FailingReader;
+
+        impl Read for FailingReader {
+            fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk on fire"))
+            }
+        }
+
+        let mut lexer = StreamingLexer::new(FailingReader);
+        match lexer.next_token() {
+            Err(StreamError::Io(error)) => assert_eq!(error.to_string(), "disk on fire"),
+            other => panic!("expected an io error, got {:?}", other),
+        }
+    }
+}
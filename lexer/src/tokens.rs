@@ -1,12 +1,99 @@
+use crate::intern::Symbol;
 use crate::span::Span;
+use phf::phf_map;
 use std::fmt::Display;
 
+/// Declares every keyword from one table of variant names and spellings, generating the
+/// [`Keyword`] enum, its lookup map and spellings, and the conversions to and from [`TokenKind`].
+/// Each keyword also needs a `TokenKind` variant of the same name, which the conversions require.
+macro_rules! keywords {
+    ($($variant:ident => $spelling:tt,)*) => {
+        /// Reserved words of the language.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Keyword {
+            $($variant,)*
+        }
+
+        /// Static map for keywords
+        static KEYWORDS: phf::Map<&'static str, Keyword> = phf_map! {
+            $($spelling => Keyword::$variant,)*
+        };
+
+        impl Keyword {
+            /// Every keyword, in declaration order.
+            pub const ALL: [Keyword; [$(Keyword::$variant),*].len()] = [$(Keyword::$variant),*];
+
+            /// Get the source spelling of the keyword.
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(Keyword::$variant => $spelling,)*
+                }
+            }
+
+            /// Get the name of the keyword's token kind, such as `"Val"`.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Keyword::$variant => stringify!($variant),)*
+                }
+            }
+        }
+
+        impl From<Keyword> for TokenKind {
+            fn from(keyword: Keyword) -> Self {
+                match keyword {
+                    $(Keyword::$variant => TokenKind::$variant,)*
+                }
+            }
+        }
+
+        impl TokenKind {
+            /// Get the built-in keyword this kind of token is, or `None` if it is not one.
+            /// Words reserved with `Lexer::with_keywords` are not built-in keywords.
+            pub fn keyword(&self) -> Option<Keyword> {
+                match self {
+                    $(TokenKind::$variant => Some(Keyword::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+
+        /// Matches the token kinds of every built-in keyword.
+        macro_rules! keyword_kinds {
+            () => {
+                $(TokenKind::$variant)|*
+            };
+        }
+    };
+}
+
+keywords! {
+    Val => "val",
+    Var => "var",
+    Fn => "fn",
+    If => "if",
+    Elif => "elif",
+    Else => "else",
+}
+
+/// Look up the keyword spelled by `word`.
+/// Returns `None` if `word` is not a keyword.
+pub fn keyword_from_str(word: &str) -> Option<Keyword> {
+    KEYWORDS.get(word).copied()
+}
+
+impl Display for Keyword {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TokenKind {
     // Keywords
-    Val, // val
-    Var, // var
-    Fn,  // fn
+    Val,                   // val
+    Var,                   // var
+    Fn,                    // fn
+    CustomKeyword(String), // words reserved with `Lexer::with_keywords`
 
     // Control flow
     If,   // if
@@ -14,40 +101,229 @@ pub enum TokenKind {
     Else, // else
 
     // Syntax
-    LParen,   // (
-    RParen,   // )
-    LBracket, // [
-    RBracket, // ]
-    LBrace,   // {
-    RBrace,   // }
-    Comma,    // ,
-    Colon,    // :
-    Assign,   // =
+    LParen,     // (
+    RParen,     // )
+    LBracket,   // [
+    RBracket,   // ]
+    LBrace,     // {
+    RBrace,     // }
+    Comma,      // ,
+    Colon,      // :
+    Assign,     // =
+    Underscore, // _
+    At,         // @, introducing an attribute
 
     // Operators
     Plus,               // +
     Minus,              // -
     Multiply,           // *
     Exponent,           // ^
+    BitXor,             // ~, as `^` is already exponentiation
     Divide,             // /
     Modulus,            // %
+    ModAssign,          // %=
     Equals,             // ==
     NotEquals,          // !=
     LessThan,           // <
     GreaterThan,        // >
     LessThanOrEqual,    // <=
     GreaterThanOrEqual, // >=
+    ShiftLeft,          // <<
+    ShiftRight,         // >>, which a parser can split with `TokenStream::split_gt`
     And,                // &&
     Or,                 // ||
     Not,                // !
+    Question,           // ?, for optional types and error propagation
+    FatArrow,           // =>, for match arms and closures
 
     // Identifiers
-    Identifier(String), // variable names, function names, etc.
-    IntLiteral(isize),  // integer literals
+    Identifier(String),         // variable names, function names, etc.
+    InternedIdentifier(Symbol), // identifiers when interning, shown by symbol, not `as_identifier`
+    IntLiteral(isize),          // integer literals, never negative: `-5` is `Minus` then `5`
+    BigIntLiteral(String),      // the digits of integer literals too large for `IntLiteral`
+    StringLiteral(String),      // string literals, with escapes resolved
+    CharLiteral(char),          // character literals, with escapes resolved
+
+    // Layout, only emitted in indentation mode
+    Newline, // end of a logical line
+    Indent,  // start of an indented block
+    Dedent,  // end of an indented block
+
+    // Trivia
+    Whitespace,         // a run of whitespace, only emitted when requested
+    DocComment(String), // the text of a `///` comment after the slashes
+    Comment(String),    // the text of a `//` comment after the slashes, only emitted when requested
 
     Eof, // End of file
 }
 
+/// Broad groups of token kinds, for tools such as syntax highlighters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenCategory {
+    Keyword,
+    Operator,
+    Literal,
+    Delimiter,
+    Identifier,
+    Trivia,
+}
+
+/// How a binary operator groups when it appears several times in a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`.
+    Left,
+    /// `a ^ b ^ c` is `a ^ (b ^ c)`.
+    Right,
+    /// `a == b == c` is an error, and must be written with parentheses or `&&`.
+    NonAssociative,
+}
+
+impl TokenKind {
+    /// Get the category this kind of token belongs to.
+    /// Layout tokens and `Eof` are delimiters, as they separate constructs.
+    pub fn category(&self) -> TokenCategory {
+        match self {
+            keyword_kinds!() | TokenKind::CustomKeyword(_) => TokenCategory::Keyword,
+            TokenKind::LParen
+            | TokenKind::RParen
+            | TokenKind::LBracket
+            | TokenKind::RBracket
+            | TokenKind::LBrace
+            | TokenKind::RBrace
+            | TokenKind::Comma
+            | TokenKind::Colon
+            | TokenKind::At
+            | TokenKind::Newline
+            | TokenKind::Indent
+            | TokenKind::Dedent
+            | TokenKind::Eof => TokenCategory::Delimiter,
+            TokenKind::Assign
+            | TokenKind::Plus
+            | TokenKind::Minus
+            | TokenKind::Multiply
+            | TokenKind::Exponent
+            | TokenKind::BitXor
+            | TokenKind::Divide
+            | TokenKind::Modulus
+            | TokenKind::ModAssign
+            | TokenKind::Equals
+            | TokenKind::NotEquals
+            | TokenKind::LessThan
+            | TokenKind::GreaterThan
+            | TokenKind::LessThanOrEqual
+            | TokenKind::GreaterThanOrEqual
+            | TokenKind::ShiftLeft
+            | TokenKind::ShiftRight
+            | TokenKind::And
+            | TokenKind::Or
+            | TokenKind::Not
+            | TokenKind::Question
+            | TokenKind::FatArrow => TokenCategory::Operator,
+            TokenKind::Identifier(_) | TokenKind::InternedIdentifier(_) | TokenKind::Underscore => {
+                TokenCategory::Identifier
+            }
+            TokenKind::IntLiteral(_)
+            | TokenKind::BigIntLiteral(_)
+            | TokenKind::StringLiteral(_)
+            | TokenKind::CharLiteral(_) => TokenCategory::Literal,
+            TokenKind::Whitespace | TokenKind::DocComment(_) | TokenKind::Comment(_) => {
+                TokenCategory::Trivia
+            }
+        }
+    }
+
+    /// Check if this kind of token can be a prefix operator, applying to the operand after it.
+    /// The lexer never folds a sign into a literal, since whether `a -5` subtracts or negates
+    /// depends on the grammar, so the parser uses this to treat `-` as negation.
+    pub fn is_prefix_operator(&self) -> bool {
+        matches!(self, TokenKind::Minus | TokenKind::Not)
+    }
+
+    /// Get the binding power and associativity of this kind of token as a binary operator,
+    /// or `None` if it is not one. Higher binding powers bind more tightly.
+    /// Comparisons do not chain, so `a == b == c` is reported at the second comparison
+    /// rather than read as `(a == b) == c`, by `TokenStream::parse_expression`.
+    pub fn infix_binding(&self) -> Option<(u8, Associativity)> {
+        use Associativity::*;
+
+        let binding = match self {
+            TokenKind::Or => (1, Left),
+            TokenKind::And => (2, Left),
+            TokenKind::Equals
+            | TokenKind::NotEquals
+            | TokenKind::LessThan
+            | TokenKind::GreaterThan
+            | TokenKind::LessThanOrEqual
+            | TokenKind::GreaterThanOrEqual => (3, NonAssociative),
+            TokenKind::BitXor => (4, Left),
+            TokenKind::ShiftLeft | TokenKind::ShiftRight => (5, Left),
+            TokenKind::Plus | TokenKind::Minus => (6, Left),
+            TokenKind::Multiply | TokenKind::Divide | TokenKind::Modulus => (7, Left),
+            TokenKind::Exponent => (8, Right),
+            _ => return None,
+        };
+        Some(binding)
+    }
+
+    /// Get the name of this kind of token, the same for any payload, such as `"IntLiteral"`.
+    /// Unlike the symbol shown by `Display`, the name is stable for tooling to refer to.
+    pub fn name(&self) -> &'static str {
+        if let Some(keyword) = self.keyword() {
+            return keyword.name();
+        }
+        match self {
+            keyword_kinds!() => unreachable!("keywords are named by `Keyword::name`"),
+            TokenKind::CustomKeyword(_) => "CustomKeyword",
+            TokenKind::LParen => "LParen",
+            TokenKind::RParen => "RParen",
+            TokenKind::LBracket => "LBracket",
+            TokenKind::RBracket => "RBracket",
+            TokenKind::LBrace => "LBrace",
+            TokenKind::RBrace => "RBrace",
+            TokenKind::Comma => "Comma",
+            TokenKind::Colon => "Colon",
+            TokenKind::Assign => "Assign",
+            TokenKind::Underscore => "Underscore",
+            TokenKind::At => "At",
+            TokenKind::Plus => "Plus",
+            TokenKind::Minus => "Minus",
+            TokenKind::Multiply => "Multiply",
+            TokenKind::Exponent => "Exponent",
+            TokenKind::BitXor => "BitXor",
+            TokenKind::Divide => "Divide",
+            TokenKind::Modulus => "Modulus",
+            TokenKind::ModAssign => "ModAssign",
+            TokenKind::Equals => "Equals",
+            TokenKind::NotEquals => "NotEquals",
+            TokenKind::LessThan => "LessThan",
+            TokenKind::GreaterThan => "GreaterThan",
+            TokenKind::LessThanOrEqual => "LessThanOrEqual",
+            TokenKind::GreaterThanOrEqual => "GreaterThanOrEqual",
+            TokenKind::ShiftLeft => "ShiftLeft",
+            TokenKind::ShiftRight => "ShiftRight",
+            TokenKind::And => "And",
+            TokenKind::Or => "Or",
+            TokenKind::Not => "Not",
+            TokenKind::Question => "Question",
+            TokenKind::FatArrow => "FatArrow",
+            TokenKind::Identifier(_) => "Identifier",
+            TokenKind::InternedIdentifier(_) => "InternedIdentifier",
+            TokenKind::IntLiteral(_) => "IntLiteral",
+            TokenKind::BigIntLiteral(_) => "BigIntLiteral",
+            TokenKind::StringLiteral(_) => "StringLiteral",
+            TokenKind::CharLiteral(_) => "CharLiteral",
+            TokenKind::Newline => "Newline",
+            TokenKind::Indent => "Indent",
+            TokenKind::Dedent => "Dedent",
+            TokenKind::Whitespace => "Whitespace",
+            TokenKind::DocComment(_) => "DocComment",
+            TokenKind::Comment(_) => "Comment",
+            TokenKind::Eof => "Eof",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Token {
     pub kind: TokenKind,
@@ -62,17 +338,78 @@ impl Token {
             pos: Span::new(start, start + size),
         }
     }
+
+    /// Check if the token has the given kind, including any payload.
+    pub fn is(&self, kind: &TokenKind) -> bool {
+        self.kind == *kind
+    }
+
+    /// Check if the token has the same kind as `other`, including any payload, wherever each is.
+    pub fn same_kind(&self, other: &Token) -> bool {
+        self.kind == other.kind
+    }
+
+    /// Check if the token marks the end of the input.
+    pub fn is_eof(&self) -> bool {
+        self.kind == TokenKind::Eof
+    }
+
+    /// Check if the token is a keyword.
+    pub fn is_keyword(&self) -> bool {
+        self.kind.category() == TokenCategory::Keyword
+    }
+
+    /// Check if the token is an operator.
+    pub fn is_operator(&self) -> bool {
+        self.kind.category() == TokenCategory::Operator
+    }
+
+    /// Check if the token is an identifier, whether or not it was interned.
+    pub fn is_identifier(&self) -> bool {
+        matches!(
+            self.kind,
+            TokenKind::Identifier(_) | TokenKind::InternedIdentifier(_)
+        )
+    }
+
+    /// Get the value of an `IntLiteral` token.
+    /// Returns `None` for any other kind of token, including big integer literals.
+    pub fn int_value(&self) -> Option<isize> {
+        match self.kind {
+            TokenKind::IntLiteral(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the value of an `IntLiteral` token converted to `T`, such as `u8` or `i32`.
+    /// Returns `None` for any other kind of token, or if the value is out of range for `T`.
+    pub fn int_value_as<T: TryFrom<isize>>(&self) -> Option<T> {
+        T::try_from(self.int_value()?).ok()
+    }
+
+    /// Get the name of an `Identifier` token.
+    /// Returns `None` for any other kind of token, including interned identifiers,
+    /// whose names are resolved by the lexer's `Interner`. Use `is_identifier` to match both.
+    pub fn as_identifier(&self) -> Option<&str> {
+        match &self.kind {
+            TokenKind::Identifier(name) => Some(name),
+            _ => None,
+        }
+    }
 }
 
+/// Shows a token kind as it is written in source, such as `==` or `val`.
+/// An interned identifier shows as its symbol, such as `#0`, since only the lexer's `Interner`
+/// knows its name, and so does any error message holding one: resolve it there,
+/// or pass the interner to `stream::dump_tokens`.
 impl Display for TokenKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(keyword) = self.keyword() {
+            return write!(f, "{}", keyword);
+        }
         let str = match self {
-            TokenKind::Val => "val",
-            TokenKind::Var => "var",
-            TokenKind::Fn => "fn",
-            TokenKind::If => "if",
-            TokenKind::Elif => "elif",
-            TokenKind::Else => "else",
+            keyword_kinds!() => unreachable!("keywords are spelled by `Keyword::as_str`"),
+            TokenKind::CustomKeyword(word) => word,
             TokenKind::LParen => "(",
             TokenKind::RParen => ")",
             TokenKind::LBracket => "[",
@@ -82,23 +419,41 @@ impl Display for TokenKind {
             TokenKind::Comma => ",",
             TokenKind::Colon => ":",
             TokenKind::Assign => "=",
+            TokenKind::Underscore => "_",
+            TokenKind::At => "@",
             TokenKind::Plus => "+",
             TokenKind::Minus => "-",
             TokenKind::Multiply => "*",
             TokenKind::Exponent => "^",
+            TokenKind::BitXor => "~",
             TokenKind::Divide => "/",
             TokenKind::Modulus => "%",
+            TokenKind::ModAssign => "%=",
             TokenKind::Equals => "==",
             TokenKind::NotEquals => "!=",
             TokenKind::LessThan => "<",
             TokenKind::GreaterThan => ">",
             TokenKind::LessThanOrEqual => "<=",
             TokenKind::GreaterThanOrEqual => ">=",
+            TokenKind::ShiftLeft => "<<",
+            TokenKind::ShiftRight => ">>",
             TokenKind::And => "&&",
             TokenKind::Or => "||",
             TokenKind::Not => "!",
+            TokenKind::Question => "?",
+            TokenKind::FatArrow => "=>",
             TokenKind::Identifier(name) => name,
+            TokenKind::InternedIdentifier(symbol) => &symbol.to_string(),
             TokenKind::IntLiteral(value) => &value.to_string(),
+            TokenKind::BigIntLiteral(digits) => digits,
+            TokenKind::StringLiteral(value) => &format!("{:?}", value),
+            TokenKind::CharLiteral(value) => &format!("{:?}", value),
+            TokenKind::Newline => "newline",
+            TokenKind::Indent => "indent",
+            TokenKind::Dedent => "dedent",
+            TokenKind::Whitespace => "whitespace",
+            TokenKind::DocComment(text) => &format!("///{}", text),
+            TokenKind::Comment(text) => &format!("//{}", text),
             TokenKind::Eof => "EOF",
         };
         write!(f, "{}", str)
@@ -126,4 +481,182 @@ mod tests {
         let token = Token::new(TokenKind::Val, 0, 3);
         assert_eq!(token.to_string(), "val [0..3]");
     }
+
+    #[test]
+    fn test_keyword_lookup() {
+        for keyword in Keyword::ALL {
+            let kind = TokenKind::from(keyword);
+            assert_eq!(keyword_from_str(keyword.as_str()), Some(keyword));
+            assert_eq!(kind.to_string(), keyword.as_str());
+            assert_eq!(kind.keyword(), Some(keyword));
+            assert_eq!(kind.name(), keyword.name());
+        }
+        assert_eq!(KEYWORDS.len(), Keyword::ALL.len());
+        assert_eq!(TokenKind::Elif.name(), "Elif");
+        assert_eq!(
+            TokenKind::CustomKeyword("match".to_string()).keyword(),
+            None
+        );
+        assert_eq!(TokenKind::Plus.keyword(), None);
+    }
+
+    #[test]
+    fn test_keyword_lookup_near_miss() {
+        assert_eq!(keyword_from_str("Val"), None);
+        assert_eq!(keyword_from_str("vals"), None);
+        assert_eq!(keyword_from_str("el"), None);
+        assert_eq!(keyword_from_str(""), None);
+    }
+
+    #[test]
+    fn test_token_category() {
+        assert_eq!(TokenKind::Fn.category(), TokenCategory::Keyword);
+        assert_eq!(TokenKind::Elif.category(), TokenCategory::Keyword);
+        assert_eq!(TokenKind::Plus.category(), TokenCategory::Operator);
+        assert_eq!(TokenKind::Assign.category(), TokenCategory::Operator);
+        assert_eq!(TokenKind::FatArrow.category(), TokenCategory::Operator);
+        assert_eq!(TokenKind::IntLiteral(5).category(), TokenCategory::Literal);
+        assert_eq!(TokenKind::LBrace.category(), TokenCategory::Delimiter);
+        assert_eq!(TokenKind::Dedent.category(), TokenCategory::Delimiter);
+        assert_eq!(
+            TokenKind::Identifier("x".to_string()).category(),
+            TokenCategory::Identifier
+        );
+        assert_eq!(TokenKind::Whitespace.category(), TokenCategory::Trivia);
+        assert_eq!(
+            TokenKind::DocComment(" docs".to_string()).category(),
+            TokenCategory::Trivia
+        );
+    }
+
+    #[test]
+    fn test_keywords_are_keyword_category() {
+        for keyword in Keyword::ALL {
+            assert_eq!(TokenKind::from(keyword).category(), TokenCategory::Keyword);
+        }
+    }
+
+    #[test]
+    fn test_token_kind_names() {
+        assert_eq!(TokenKind::Plus.name(), "Plus");
+        assert_eq!(TokenKind::GreaterThanOrEqual.name(), "GreaterThanOrEqual");
+        assert_eq!(TokenKind::Identifier("x".to_string()).name(), "Identifier");
+        assert_eq!(TokenKind::IntLiteral(5).name(), "IntLiteral");
+        assert_eq!(TokenKind::IntLiteral(-1).name(), "IntLiteral");
+        assert_eq!(TokenKind::Eof.name(), "Eof");
+    }
+
+    #[test]
+    fn test_infix_binding() {
+        let power = |kind: TokenKind| kind.infix_binding().unwrap().0;
+
+        assert!(power(TokenKind::Multiply) > power(TokenKind::Plus));
+        assert!(power(TokenKind::Plus) > power(TokenKind::LessThan));
+        assert!(power(TokenKind::Equals) > power(TokenKind::And));
+        assert!(power(TokenKind::And) > power(TokenKind::Or));
+        assert_eq!(
+            TokenKind::Equals.infix_binding(),
+            TokenKind::GreaterThanOrEqual.infix_binding()
+        );
+        assert_eq!(
+            TokenKind::NotEquals.infix_binding().unwrap().1,
+            Associativity::NonAssociative
+        );
+        assert_eq!(
+            TokenKind::Exponent.infix_binding().unwrap().1,
+            Associativity::Right
+        );
+        assert_eq!(TokenKind::Not.infix_binding(), None);
+        assert_eq!(TokenKind::Assign.infix_binding(), None);
+    }
+
+    #[test]
+    fn test_prefix_operators() {
+        assert!(TokenKind::Minus.is_prefix_operator());
+        assert!(TokenKind::Not.is_prefix_operator());
+        assert!(!TokenKind::Plus.is_prefix_operator());
+        assert!(!TokenKind::IntLiteral(5).is_prefix_operator());
+    }
+
+    #[test]
+    fn test_token_predicates() {
+        let eof = Token::new(TokenKind::Eof, 9, 0);
+        let keyword = Token::new(TokenKind::Val, 0, 3);
+        let operator = Token::new(TokenKind::Plus, 4, 1);
+        let identifier = Token::new(TokenKind::Identifier("x".to_string()), 6, 1);
+
+        assert!(eof.is_eof());
+        assert!(!keyword.is_eof());
+
+        assert!(keyword.is_keyword());
+        assert!(!identifier.is_keyword());
+
+        assert!(operator.is_operator());
+        assert!(!keyword.is_operator());
+
+        assert!(operator.is(&TokenKind::Plus));
+        assert!(!operator.is(&TokenKind::Minus));
+        assert!(identifier.is(&TokenKind::Identifier("x".to_string())));
+        assert!(!identifier.is(&TokenKind::Identifier("y".to_string())));
+    }
+
+    #[test]
+    fn test_token_same_kind() {
+        let a = Token::new(TokenKind::IntLiteral(1), 0, 1);
+        let b = Token::new(TokenKind::IntLiteral(1), 7, 3);
+        let c = Token::new(TokenKind::IntLiteral(2), 0, 1);
+
+        assert!(a.same_kind(&b));
+        assert_ne!(a, b);
+        assert!(!a.same_kind(&c));
+    }
+
+    #[test]
+    fn test_token_int_value() {
+        let token = Token::new(TokenKind::IntLiteral(200), 0, 3);
+
+        assert_eq!(token.int_value(), Some(200));
+        assert_eq!(token.int_value_as::<u8>(), Some(200));
+        assert_eq!(token.int_value_as::<i32>(), Some(200));
+        assert_eq!(token.int_value_as::<usize>(), Some(200));
+        assert_eq!(token.int_value_as::<i8>(), None);
+
+        let big = Token::new(TokenKind::IntLiteral(70_000), 0, 5);
+        assert_eq!(big.int_value_as::<u16>(), None);
+        assert_eq!(big.int_value_as::<u32>(), Some(70_000));
+    }
+
+    #[test]
+    fn test_token_int_value_other_kinds() {
+        let digits = Token::new(TokenKind::BigIntLiteral("1".repeat(30)), 0, 30);
+        let plus = Token::new(TokenKind::Plus, 0, 1);
+
+        assert_eq!(digits.int_value(), None);
+        assert_eq!(plus.int_value(), None);
+        assert_eq!(plus.int_value_as::<u8>(), None);
+    }
+
+    #[test]
+    fn test_token_as_identifier() {
+        let identifier = Token::new(TokenKind::Identifier("count".to_string()), 0, 5);
+        assert_eq!(identifier.as_identifier(), Some("count"));
+        assert_eq!(Token::new(TokenKind::Fn, 0, 2).as_identifier(), None);
+    }
+
+    #[test]
+    fn test_token_is_identifier() {
+        let mut interner = crate::intern::Interner::new();
+        let interned = Token::new(
+            TokenKind::InternedIdentifier(interner.intern("count")),
+            0,
+            5,
+        );
+        let identifier = Token::new(TokenKind::Identifier("count".to_string()), 0, 5);
+
+        assert!(identifier.is_identifier());
+        assert!(interned.is_identifier());
+        assert_eq!(interned.as_identifier(), None);
+        assert!(!Token::new(TokenKind::Underscore, 0, 1).is_identifier());
+        assert!(!Token::new(TokenKind::Val, 0, 3).is_identifier());
+    }
 }